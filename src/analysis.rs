@@ -0,0 +1,358 @@
+use std::f32::consts::PI;
+use std::sync::atomic::AtomicU32;
+
+use vst::util::AtomicFloat;
+
+use crate::param::MeterBallistics;
+use crate::util::{one_pole_coef, sanitize, to_db, to_linear};
+
+/*
+ * Level measurement shared by the meters
+ */
+
+pub fn block_peak(samples: &[f32]) -> f32 {
+    samples
+        .iter()
+        .fold(0.0, |peak, sample| f32::max(peak, sample.abs()))
+}
+
+pub fn block_rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum: f32 = samples.iter().map(|sample| sample * sample).sum();
+    (sum / samples.len() as f32).sqrt()
+}
+
+// True peak, the output upsampled 4x with a windowed sinc so the peaks a DAC
+// reconstructs between samples show up too, only used for the meter
+const TRUE_PEAK_FACTOR: usize = 4;
+// taps per phase
+const TRUE_PEAK_TAPS: usize = 12;
+
+#[derive(Clone, Copy)]
+pub struct TruePeak {
+    phases: [[f32; TRUE_PEAK_TAPS]; TRUE_PEAK_FACTOR],
+    // the last TRUE_PEAK_TAPS input samples, newest at pos
+    history: [f32; TRUE_PEAK_TAPS],
+    pos: usize,
+}
+
+impl Default for TruePeak {
+    fn default() -> TruePeak {
+        let len = TRUE_PEAK_TAPS * TRUE_PEAK_FACTOR;
+        let center = (len - 1) as f32 / 2.0;
+        let mut phases = [[0.0; TRUE_PEAK_TAPS]; TRUE_PEAK_FACTOR];
+        for (index, tap) in (0..len).map(|index| (index, index as f32)) {
+            // time in input samples, the sinc cuts off at the original nyquist
+            let t = (tap - center) / TRUE_PEAK_FACTOR as f32;
+            let sinc = if t == 0.0 {
+                1.0
+            } else {
+                (PI * t).sin() / (PI * t)
+            };
+            // Blackman window
+            let x = 2.0 * PI * tap / (len - 1) as f32;
+            let window = 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos();
+            phases[index % TRUE_PEAK_FACTOR][index / TRUE_PEAK_FACTOR] = sinc * window;
+        }
+        TruePeak {
+            phases,
+            history: [0.0; TRUE_PEAK_TAPS],
+            pos: 0,
+        }
+    }
+}
+
+impl TruePeak {
+    // highest upsampled level in the block, history carries over to the next one
+    pub fn block_peak(&mut self, samples: &[f32]) -> f32 {
+        let mut peak: f32 = 0.0;
+        for sample in samples {
+            self.pos = (self.pos + 1) % TRUE_PEAK_TAPS;
+            self.history[self.pos] = *sample;
+            for phase in &self.phases {
+                let interpolated: f32 = phase
+                    .iter()
+                    .enumerate()
+                    .map(|(age, coef)| {
+                        coef * self.history[(self.pos + TRUE_PEAK_TAPS - age) % TRUE_PEAK_TAPS]
+                    })
+                    .sum();
+                peak = peak.max(interpolated.abs());
+            }
+            peak = peak.max(sample.abs());
+        }
+        peak
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; TRUE_PEAK_TAPS];
+    }
+}
+
+// Smoothed 0..1 level of a signal, reported once per block as a control value
+#[derive(Clone, Copy)]
+pub struct EnvelopeFollower {
+    value: f32,
+    attack_coef: f32,
+    release_coef: f32,
+}
+
+impl Default for EnvelopeFollower {
+    fn default() -> EnvelopeFollower {
+        EnvelopeFollower {
+            value: 0.0,
+            attack_coef: 1.0,
+            release_coef: 1.0,
+        }
+    }
+}
+
+impl EnvelopeFollower {
+    pub fn set_times(&mut self, attack_ms: f32, release_ms: f32, sample_rate: f32) {
+        self.attack_coef = one_pole_coef(attack_ms, sample_rate);
+        self.release_coef = one_pole_coef(release_ms, sample_rate);
+    }
+
+    // follows the louder side sample by sample, returns where it ended up
+    pub fn process_block(&mut self, (buf_l, buf_r): (&[f32], &[f32])) -> f32 {
+        for (sample_l, sample_r) in buf_l.iter().zip(buf_r) {
+            let level = sample_l.abs().max(sample_r.abs()).min(1.0);
+            let coef = if level > self.value {
+                self.attack_coef
+            } else {
+                self.release_coef
+            };
+            self.value += (level - self.value) * coef;
+        }
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+    }
+
+    pub fn sanitize(&mut self) -> bool {
+        sanitize(&mut self.value)
+    }
+}
+
+// PPM, rises within about 10 ms and falls 20 dB in 2.8 s
+const PPM_ATTACK_MS: f32 = 10.0;
+const PPM_FALL_DB_PER_SEC: f32 = 20.0 / 2.8;
+// VU, the time constant that gets a step to 99% of its reading in about 300 ms
+const VU_TIME_MS: f32 = 65.0;
+
+// A level readout with meter ballistics, runs on the editor's frames instead of samples
+#[derive(Clone, Copy, Default)]
+pub struct BallisticLevel {
+    value: f32,
+}
+
+impl BallisticLevel {
+    // dt is the time since the last frame in seconds, returns the level to show
+    pub fn update(&mut self, level: f32, ballistics: MeterBallistics, dt: f32) -> f32 {
+        // one_pole_coef counts in samples, here one frame is one sample
+        let frame_rate = 1.0 / dt.max(f32::EPSILON);
+        self.value = match ballistics {
+            MeterBallistics::Digital => level,
+            MeterBallistics::Ppm => {
+                if level > self.value {
+                    self.value + (level - self.value) * one_pole_coef(PPM_ATTACK_MS, frame_rate)
+                } else {
+                    let fallen = to_linear(to_db(self.value) - PPM_FALL_DB_PER_SEC * dt);
+                    fallen.max(level)
+                }
+            }
+            MeterBallistics::Vu => {
+                self.value + (level - self.value) * one_pole_coef(VU_TIME_MS, frame_rate)
+            }
+        };
+        if !sanitize(&mut self.value) {
+            self.value = 0.0;
+        }
+        self.value
+    }
+}
+
+// levels of the last processed block, written by process() and read by the editor
+pub struct Meters {
+    pub input_peak: AtomicFloat,
+    pub input_rms: AtomicFloat,
+    pub output_peak: AtomicFloat,
+    pub output_rms: AtomicFloat,
+    pub output_true_peak: AtomicFloat,
+    // highest peak since the editor last reset them
+    pub input_peak_hold: AtomicFloat,
+    pub output_peak_hold: AtomicFloat,
+    pub output_true_peak_hold: AtomicFloat,
+    // how many dB the first clip stage took off each channel's peak, zero when it didn't clip
+    pub clip_reduction_l: AtomicFloat,
+    pub clip_reduction_r: AtomicFloat,
+    // peak going into the first clip stage, after everything that pushes into it
+    pub clip_input_peak: AtomicFloat,
+    pub clip_input_peak_hold: AtomicFloat,
+    // where the first clip stage actually clips, after the amount macro and the drift
+    pub clip_threshold: AtomicFloat,
+    // envelope of the output, 0..1, for anything that wants to follow the level
+    pub envelope: AtomicFloat,
+    // how often the DSP state went non-finite and had to start over, the editor reports it
+    pub state_resets: AtomicU32,
+}
+
+impl Default for Meters {
+    fn default() -> Meters {
+        Meters {
+            input_peak: AtomicFloat::new(0.0),
+            input_rms: AtomicFloat::new(0.0),
+            output_peak: AtomicFloat::new(0.0),
+            output_rms: AtomicFloat::new(0.0),
+            output_true_peak: AtomicFloat::new(0.0),
+            input_peak_hold: AtomicFloat::new(0.0),
+            output_peak_hold: AtomicFloat::new(0.0),
+            output_true_peak_hold: AtomicFloat::new(0.0),
+            clip_reduction_l: AtomicFloat::new(0.0),
+            clip_reduction_r: AtomicFloat::new(0.0),
+            clip_input_peak: AtomicFloat::new(0.0),
+            clip_input_peak_hold: AtomicFloat::new(0.0),
+            clip_threshold: AtomicFloat::new(0.0),
+            envelope: AtomicFloat::new(0.0),
+            state_resets: AtomicU32::new(0),
+        }
+    }
+}
+
+impl Meters {
+    pub fn write_input(&self, (buf_l, buf_r): (&[f32], &[f32])) {
+        let peak = block_peak(buf_l).max(block_peak(buf_r));
+        self.input_peak.set(peak);
+        self.input_rms.set(stereo_rms(buf_l, buf_r));
+        hold_peak(&self.input_peak_hold, peak);
+    }
+
+    pub fn write_output(&self, (buf_l, buf_r): (&[f32], &[f32]), true_peak: f32) {
+        let peak = block_peak(buf_l).max(block_peak(buf_r));
+        self.output_peak.set(peak);
+        self.output_rms.set(stereo_rms(buf_l, buf_r));
+        self.output_true_peak.set(true_peak);
+        hold_peak(&self.output_peak_hold, peak);
+        hold_peak(&self.output_true_peak_hold, true_peak);
+    }
+
+    // left and right are kept apart so an off-center mix shows which side clips
+    pub fn write_clip(
+        &self,
+        (in_l, in_r): (&[f32], &[f32]),
+        (out_l, out_r): (&[f32], &[f32]),
+        threshold: f32,
+    ) {
+        self.clip_reduction_l.set(peak_reduction(in_l, out_l));
+        self.clip_reduction_r.set(peak_reduction(in_r, out_r));
+        let peak = block_peak(in_l).max(block_peak(in_r));
+        self.clip_input_peak.set(peak);
+        hold_peak(&self.clip_input_peak_hold, peak);
+        self.clip_threshold.set(threshold);
+    }
+
+    // called from the editor, back to silence
+    pub fn reset_peak_hold(&self) {
+        self.input_peak_hold.set(0.0);
+        self.output_peak_hold.set(0.0);
+        self.output_true_peak_hold.set(0.0);
+        self.clip_input_peak_hold.set(0.0);
+    }
+}
+
+// only the audio thread raises the hold, a reset racing it just loses one block
+fn hold_peak(hold: &AtomicFloat, peak: f32) {
+    if peak > hold.get() {
+        hold.set(peak);
+    }
+}
+
+// how far the clipper input peaks over the threshold in dB, negative while there is headroom left
+pub fn clip_over_db(clip_input_peak: f32, threshold: f32) -> f32 {
+    to_db(clip_input_peak) - to_db(threshold)
+}
+
+// how many dB to trim by so a measured peak lands right on the threshold, infinite for silence
+pub fn trim_to_threshold_db(peak: f32, threshold: f32) -> f32 {
+    -clip_over_db(peak, threshold)
+}
+
+// dB between the peaks going in and coming out, never negative
+fn peak_reduction(input: &[f32], output: &[f32]) -> f32 {
+    let (in_peak, out_peak) = (block_peak(input), block_peak(output));
+    if out_peak < in_peak {
+        to_db(in_peak) - to_db(out_peak)
+    } else {
+        0.0
+    }
+}
+
+fn stereo_rms(buf_l: &[f32], buf_r: &[f32]) -> f32 {
+    let (rms_l, rms_r) = (block_rms(buf_l), block_rms(buf_r));
+    ((rms_l * rms_l + rms_r * rms_r) / 2.0).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_1_SQRT_2, TAU};
+
+    use super::*;
+
+    #[test]
+    fn block_levels_of_silence_dc_and_sine() {
+        assert_eq!(block_peak(&[0.0; 64]), 0.0);
+        assert_eq!(block_rms(&[0.0; 64]), 0.0);
+        assert_eq!(block_rms(&[]), 0.0);
+        // DC reads the same either way, whichever side it's on
+        assert_eq!(block_peak(&[-0.5; 64]), 0.5);
+        assert!((block_rms(&[-0.5; 64]) - 0.5).abs() < 1e-6);
+        // a whole number of cycles of a sine, rms is the peak over root 2
+        let sine: Vec<f32> = (0..1000)
+            .map(|index| 0.8 * (TAU * index as f32 / 100.0).sin())
+            .collect();
+        assert!((block_peak(&sine) - 0.8).abs() < 1e-3);
+        assert!((block_rms(&sine) - 0.8 / 2.0_f32.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn true_peak_finds_the_crest_between_samples() {
+        // a quarter of the sample rate shifted by 45 degrees, every sample lands at
+        // 1/sqrt(2) of the crest and the crest itself sits half way between two
+        let sine: Vec<f32> = (0..1024)
+            .map(|index| (TAU * ((index % 4) as f32 / 4.0 + 0.125)).sin())
+            .collect();
+        assert!((block_peak(&sine) - FRAC_1_SQRT_2).abs() < 1e-6);
+        let mut true_peak = TruePeak::default();
+        // the first block fills the history
+        true_peak.block_peak(&sine[..512]);
+        assert!((true_peak.block_peak(&sine[512..]) - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn clip_over_below_at_and_above_the_threshold() {
+        // 6 dB of headroom left, right on it, 6 dB into the clipper
+        assert!((clip_over_db(0.25, 0.5) + 6.0206).abs() < 1e-3);
+        assert!(clip_over_db(0.5, 0.5).abs() < 1e-6);
+        assert!((clip_over_db(1.0, 0.5) - 6.0206).abs() < 1e-3);
+        // nothing coming in reads as all headroom
+        assert_eq!(clip_over_db(0.0, 0.5), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn trim_lands_the_peak_on_the_threshold() {
+        // 6 dB under wants 6 dB more, 6 dB over wants 6 dB less
+        assert!((trim_to_threshold_db(0.25, 0.5) - 6.0206).abs() < 1e-3);
+        assert!((trim_to_threshold_db(1.0, 0.5) + 6.0206).abs() < 1e-3);
+        assert!(trim_to_threshold_db(0.5, 0.5).abs() < 1e-6);
+        for (peak, threshold) in [(0.1, 0.7), (0.9, 0.05), (0.03, 0.03)] {
+            let trimmed = peak * to_linear(trim_to_threshold_db(peak, threshold));
+            assert!((trimmed - threshold).abs() < 1e-4);
+        }
+        // nothing measured yet, nothing to trim to
+        assert!(!trim_to_threshold_db(0.0, 0.5).is_finite());
+    }
+}
@@ -0,0 +1,134 @@
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+use crate::util::sanitize;
+
+/*
+ * Reusable filters
+ * Coefficients follow the RBJ audio EQ cookbook
+ */
+
+// Transposed direct form II biquad
+#[derive(Clone, Copy)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Default for Biquad {
+    fn default() -> Biquad {
+        // passes the signal through untouched until coefficients are set
+        Biquad {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+}
+
+impl Biquad {
+    pub fn set_low_pass(&mut self, freq: f32, q: f32, sample_rate: f32) {
+        let (cos_w, alpha) = prewarp(freq, q, sample_rate);
+        let a0 = 1.0 + alpha;
+        self.b0 = (1.0 - cos_w) / 2.0 / a0;
+        self.b1 = (1.0 - cos_w) / a0;
+        self.b2 = self.b0;
+        self.a1 = -2.0 * cos_w / a0;
+        self.a2 = (1.0 - alpha) / a0;
+    }
+
+    pub fn set_high_pass(&mut self, freq: f32, q: f32, sample_rate: f32) {
+        let (cos_w, alpha) = prewarp(freq, q, sample_rate);
+        let a0 = 1.0 + alpha;
+        self.b0 = (1.0 + cos_w) / 2.0 / a0;
+        self.b1 = -(1.0 + cos_w) / a0;
+        self.b2 = self.b0;
+        self.a1 = -2.0 * cos_w / a0;
+        self.a2 = (1.0 - alpha) / a0;
+    }
+
+    // gain in dB above the corner, a +g / -g pair with the same corner cancels out exactly
+    pub fn set_high_shelf(&mut self, freq: f32, q: f32, gain_db: f32, sample_rate: f32) {
+        let (cos_w, alpha) = prewarp(freq, q, sample_rate);
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        let sqrt_alpha = 2.0 * a.sqrt() * alpha;
+        let a0 = (a + 1.0) - (a - 1.0) * cos_w + sqrt_alpha;
+        self.b0 = a * ((a + 1.0) + (a - 1.0) * cos_w + sqrt_alpha) / a0;
+        self.b1 = -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w) / a0;
+        self.b2 = a * ((a + 1.0) + (a - 1.0) * cos_w - sqrt_alpha) / a0;
+        self.a1 = 2.0 * ((a - 1.0) - (a + 1.0) * cos_w) / a0;
+        self.a2 = ((a + 1.0) - (a - 1.0) * cos_w - sqrt_alpha) / a0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    pub fn sanitize(&mut self) -> bool {
+        sanitize(&mut self.z1) & sanitize(&mut self.z2)
+    }
+}
+
+// returns cos(w0) and alpha for the given corner
+fn prewarp(freq: f32, q: f32, sample_rate: f32) -> (f32, f32) {
+    // keep the corner safely below nyquist
+    let freq = freq.clamp(1.0, sample_rate * 0.45);
+    let w0 = 2.0 * PI * freq / sample_rate;
+    (w0.cos(), w0.sin() / (2.0 * q))
+}
+
+// 4th order Linkwitz-Riley crossover, low + high sums back to an allpass
+#[derive(Clone, Copy, Default)]
+pub struct Crossover {
+    low: [Biquad; 2],
+    high: [Biquad; 2],
+}
+
+impl Crossover {
+    pub fn set_frequency(&mut self, freq: f32, sample_rate: f32) {
+        for stage in &mut self.low {
+            stage.set_low_pass(freq, FRAC_1_SQRT_2, sample_rate);
+        }
+        for stage in &mut self.high {
+            stage.set_high_pass(freq, FRAC_1_SQRT_2, sample_rate);
+        }
+    }
+
+    // split a sample into (low band, high band)
+    pub fn split(&mut self, input: f32) -> (f32, f32) {
+        let [low_first, low_second] = &mut self.low;
+        let [high_first, high_second] = &mut self.high;
+        let low = low_second.process(low_first.process(input));
+        let high = high_second.process(high_first.process(input));
+        (low, high)
+    }
+
+    pub fn reset(&mut self) {
+        for stage in self.low.iter_mut().chain(self.high.iter_mut()) {
+            stage.reset();
+        }
+    }
+
+    pub fn sanitize(&mut self) -> bool {
+        self.low
+            .iter_mut()
+            .chain(self.high.iter_mut())
+            .fold(true, |is_sane, stage| stage.sanitize() & is_sane)
+    }
+}
@@ -0,0 +1,84 @@
+use std::f32::consts::TAU;
+
+use crate::param::TestSignalMode;
+use crate::util::{to_linear, Rng};
+
+/*
+ * Test signal for checking the effect without routing any audio in
+ * Replaces the input, the same signal goes to both channels
+ */
+
+const TEST_SIGNAL_DB: f32 = -12.0;
+// the sweep climbs from 20 Hz to 20 kHz over this long, then starts over
+const SWEEP_SECONDS: f32 = 10.0;
+const SWEEP_MIN_HZ: f32 = 20.0;
+const SWEEP_MAX_HZ: f32 = 20000.0;
+const NOISE_SEED: u32 = 0x7e57;
+
+pub struct TestSignal {
+    // in cycles
+    phase: f32,
+    // how far into the sweep, 0 to 1
+    sweep_position: f32,
+    rng: Rng,
+    // per instance seed the noise starts from
+    seed: u32,
+}
+
+impl Default for TestSignal {
+    fn default() -> TestSignal {
+        TestSignal {
+            phase: 0.0,
+            sweep_position: 0.0,
+            rng: Rng::new(NOISE_SEED),
+            seed: 0,
+        }
+    }
+}
+
+impl TestSignal {
+    pub fn fill(
+        &mut self,
+        (buf_l, buf_r): (&mut [f32], &mut [f32]),
+        (mode, freq): (TestSignalMode, f32),
+        sample_rate: f32,
+    ) {
+        let level = to_linear(TEST_SIGNAL_DB);
+        let sweep_step = 1.0 / (SWEEP_SECONDS * sample_rate);
+
+        for (buf_l_sample, buf_r_sample) in buf_l.iter_mut().zip(buf_r.iter_mut()) {
+            let sample = match mode {
+                TestSignalMode::Off => return,
+                TestSignalMode::Sine => self.next_sine(freq, sample_rate),
+                TestSignalMode::Sweep => {
+                    // exponential, equal time per octave
+                    let freq =
+                        SWEEP_MIN_HZ * (SWEEP_MAX_HZ / SWEEP_MIN_HZ).powf(self.sweep_position);
+                    self.sweep_position = (self.sweep_position + sweep_step).fract();
+                    self.next_sine(freq, sample_rate)
+                }
+                TestSignalMode::Noise => self.rng.next_f32() * 2.0 - 1.0,
+            };
+            *buf_l_sample = sample * level;
+            *buf_r_sample = sample * level;
+        }
+    }
+
+    fn next_sine(&mut self, freq: f32, sample_rate: f32) -> f32 {
+        let sample = (TAU * self.phase).sin();
+        self.phase = (self.phase + freq / sample_rate).fract();
+        sample
+    }
+
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.rng = Rng::new(seed ^ NOISE_SEED);
+    }
+
+    // the seed stays, the noise starts over from it
+    pub fn reset(&mut self) {
+        let seed = self.seed;
+        *self = TestSignal::default();
+        self.set_seed(seed);
+    }
+}
@@ -1,13 +1,27 @@
-/**
+/*
  * Zippify VST Plugin: lil' simple distortion/clipper VST2 Effect
  * Part of a suite "Magic 5 VST FX Plugins"
  * Author: GrieferPig
  *
  * Processing chain:
- * 1. Remove silences
- * 2. Clamp waveform (clipping)
- * 3. Decrease precision
- * 5. Mix
+ * 1. Channel routing
+ * 2. Invert polarity, then the input trim
+ * 3. Remove silences (before clipping, or after gain)
+ * 4. Glue compressor (optional)
+ * 5. Transient emphasis (optional)
+ * 6. Clamp waveform (clipping, optionally twice, with a gentle saturation below it)
+ * 7. Tape saturation
+ * 8. Wow and flutter (optional)
+ * 9. Gain (or as drive into the clamp)
+ * 10. Decrease precision (or right before clamping)
+ * 11. Safety low-pass (optional)
+ * 12. Bass mono
+ * 13. Trance gate
+ * 14. Mix (or parallel, adding only the wet highs), the dry can take the tape's tone and
+ *     the wet's loudness, compare plays the dry alone
+ * 15. Freeze
+ * 16. Dither (optional)
+ * 17. Output ceiling at the threshold (optional)
  *
  * Notes:
  * It is suggested to use this plugin with a filter because this plugin will bring
@@ -17,24 +31,43 @@
 #[macro_use]
 extern crate vst;
 
+use vst::channels::{SpeakerArrangementType, StereoChannel, StereoConfig};
 use vst::{editor::Editor, prelude::*};
 
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 
 use std::sync::Arc;
+use vst::api::TimeInfoFlags;
+use vst::host::Host;
 
+mod analysis;
+mod filter;
+mod generator;
 mod param;
 mod process;
+mod shaper;
+mod smoothing;
 mod ui;
 mod util;
 
-use crate::process::process;
-use param::{EffectParams, PARAM_NUM};
+use crate::process::{process, DspState, Transport};
+use param::{EffectParams, InputSource, PARAM_NUM};
 use ui::PluginEditor;
+use util::random_seed;
 
 struct Zippify {
     params: Arc<EffectParams>,
     editor: Option<PluginEditor>,
+    state: DspState,
+    host: HostCallback,
+    // one bit per mapped note currently down, any of them holds the freeze
+    held_notes: u128,
+    // only a freeze the notes started is theirs to let go of
+    is_midi_freeze: bool,
+    // the right side for hosts that give a single output, it's folded into the left
+    spare_out: Vec<f32>,
+    // what process() reads, copied from the input or the output buffer
+    in_copy: [Vec<f32>; 2],
 }
 
 /*
@@ -42,8 +75,10 @@ struct Zippify {
  */
 
 impl Plugin for Zippify {
-    fn new(_host: HostCallback) -> Self {
+    fn new(host: HostCallback) -> Self {
         let params = Arc::new(EffectParams::default());
+        // a fresh seed per instance, projects restore theirs through the param
+        params.seed.set(random_seed() as f32);
         Zippify {
             params: params.clone(),
             editor: Some(PluginEditor {
@@ -51,6 +86,12 @@ impl Plugin for Zippify {
                 is_open: false,
                 window_handle: None,
             }),
+            state: DspState::default(),
+            host,
+            held_notes: 0,
+            is_midi_freeze: false,
+            spare_out: Vec::new(),
+            in_copy: [Vec::new(), Vec::new()],
         }
     }
 
@@ -61,12 +102,52 @@ impl Plugin for Zippify {
             vendor: "GrieferPig".to_string(),
             inputs: 2,
             outputs: 2,
+            midi_inputs: 1,
             category: Category::Effect,
             parameters: PARAM_NUM, // num of param we have
+            preset_chunks: true,   // the instance label rides along with the params
             ..Default::default()
         }
     }
 
+    fn set_sample_rate(&mut self, rate: f32) {
+        self.state.set_sample_rate(rate);
+    }
+
+    fn set_block_size(&mut self, size: i64) {
+        let size = size.max(0) as usize;
+        self.spare_out.resize(size, 0.0);
+        for buf in &mut self.in_copy {
+            buf.resize(size, 0.0);
+        }
+        self.state.set_block_size(size);
+    }
+
+    fn can_do(&self, can_do: CanDo) -> Supported {
+        match can_do {
+            CanDo::ReceiveMidiEvent => Supported::Yes,
+            _ => Supported::Maybe,
+        }
+    }
+
+    // a mapped MIDI note holds the freeze like the button in the editor
+    fn process_events(&mut self, events: &Events) {
+        for event in events.events() {
+            if let Event::Midi(midi) = event {
+                self.handle_midi(midi.data);
+            }
+        }
+    }
+
+    // the pins are a fixed stereo pair, named so hosts wire each side to the right channel
+    fn get_input_info(&self, input: i32) -> ChannelInfo {
+        stereo_channel_info(("Input", "In"), input)
+    }
+
+    fn get_output_info(&self, output: i32) -> ChannelInfo {
+        stereo_channel_info(("Output", "Out"), output)
+    }
+
     fn get_parameter_object(&mut self) -> Arc<dyn PluginParameters> {
         Arc::clone(&self.params) as Arc<dyn PluginParameters>
     }
@@ -82,36 +163,142 @@ impl Plugin for Zippify {
     // Note: In Ableton Live, there is no sample goes into the input buffer.
     // It is stored in the output buffer instead.
     // Other DAWs may still use the input buffer so it's necessary to check the input buffer first
+    // The input source param skips the check for users who know where their host puts it
 
     fn process(&mut self, buffer: &mut AudioBuffer<f32>) {
+        let samples = buffer.samples();
         let (in_buf, mut out_buf) = buffer.split();
 
-        // make this mutable in case the input buffer is not zero
-        // let (mut in_buf_l, mut in_buf_r) = (*out_buf.get(0), *out_buf.get(1));
-        let mut in_buf_l = (*out_buf.get(0)).to_vec();
-        let mut in_buf_r = (*out_buf.get(1)).to_vec();
+        // some hosts probe with buffers that have no channels at all
+        if in_buf.is_empty() || out_buf.is_empty() {
+            return;
+        }
+        // a mono track hands over a single channel, it goes to both sides
+        let in_r_index = in_buf.len().min(2) - 1;
+        let out_r_index = out_buf.len().min(2) - 1;
+        let is_mono_out = out_r_index == 0;
 
-        let (out_buf_l, out_buf_r) = (out_buf.get_mut(0), out_buf.get_mut(1));
+        // a copy so process() can flip its polarity, sized in set_block_size like the spare below
+        let [in_copy_l, in_copy_r] = &mut self.in_copy;
+        if in_copy_l.len() < samples {
+            in_copy_l.resize(samples, 0.0);
+            in_copy_r.resize(samples, 0.0);
+        }
+        let (in_buf_l, in_buf_r) = (&mut in_copy_l[..samples], &mut in_copy_r[..samples]);
 
         // use orig input buf if not zero
         // only check 1st channel but maybe the data is in the 2nd one
         // but i don't think that's gonna happen
-        for in_buf_l_sample in in_buf.get(0).iter() {
-            if *in_buf_l_sample != 0.0 {
-                in_buf_l = (*in_buf.get(0)).to_vec();
-                in_buf_r = (*in_buf.get(1)).to_vec();
-                break;
+        let is_use_input = match InputSource::from_index(self.params.input_source.get()) {
+            InputSource::Auto => in_buf.get(0).iter().any(|sample| *sample != 0.0),
+            InputSource::Input => true,
+            InputSource::Output => false,
+        };
+        if is_use_input {
+            in_buf_l.copy_from_slice(in_buf.get(0));
+            in_buf_r.copy_from_slice(in_buf.get(in_r_index));
+        } else {
+            in_buf_l.copy_from_slice(out_buf.get(0));
+            in_buf_r.copy_from_slice(out_buf.get(out_r_index));
+        }
+
+        // a single output still gets both sides processed, the right one lands in the spare
+        // and is folded in after. Sized in set_block_size, a host going past what it
+        // announced there is the only time this allocates
+        if is_mono_out && self.spare_out.len() < samples {
+            self.spare_out.resize(samples, 0.0);
+        }
+        let (out_buf_l, out_buf_r) = if is_mono_out {
+            (out_buf.get_mut(0), &mut self.spare_out[..samples])
+        } else {
+            (out_buf.get_mut(0), out_buf.get_mut(1))
+        };
+
+        // song position for the tempo synced stages, read fresh every block so
+        // loops and jumps land on the right step instead of carrying on from the last one
+        let transport = self
+            .host
+            .get_time_info(TimeInfoFlags::PPQ_POS_VALID.bits() | TimeInfoFlags::TEMPO_VALID.bits())
+            .map(|time_info| Transport {
+                ppq_pos: if time_info.flags & TimeInfoFlags::PPQ_POS_VALID.bits() != 0 {
+                    time_info.ppq_pos
+                } else {
+                    Transport::ppq_from_samples(
+                        time_info.sample_pos,
+                        time_info.tempo,
+                        time_info.sample_rate,
+                    )
+                },
+                tempo: time_info.tempo,
+                is_playing: time_info.flags & TimeInfoFlags::TRANSPORT_PLAYING.bits() != 0,
+            })
+            .unwrap_or_default();
+
+        // the input buf stays mutable so process() can flip its polarity
+        process(
+            in_buf_l,
+            in_buf_r,
+            out_buf_l,
+            out_buf_r,
+            &self.params,
+            &mut self.state,
+            &transport,
+        );
+
+        // the same fold down as the editor's mono check
+        if is_mono_out {
+            for (out_buf_l_sample, out_buf_r_sample) in out_buf_l.iter_mut().zip(out_buf_r.iter()) {
+                *out_buf_l_sample = (*out_buf_l_sample + *out_buf_r_sample) * 0.5;
             }
         }
+    }
+}
 
-        // finalizing the input buf for security reasons
-        let in_buf_l: &[f32] = &in_buf_l;
-        let in_buf_r: &[f32] = &in_buf_r;
+impl Zippify {
+    // note ons only count on the mapped note and channel, note offs let go of whatever
+    // is held so changing the mapping mid-note can't leave the freeze stuck
+    fn handle_midi(&mut self, data: [u8; 3]) {
+        let channel = self.params.freeze_channel.get();
+        let is_mapped_channel = channel == 0.0 || (data[0] & 0x0f) as f32 == channel - 1.0;
+        let note = self.params.freeze_note.get();
+        let is_mapped_note = note < 0.0 || data[1] as f32 == note;
+        let bit = 1u128 << (data[1] & 0x7f);
+        let status = data[0] & 0xf0;
+        // a note on with zero velocity is a note off
+        let is_note_on = status == 0x90 && data[2] > 0;
+        let is_note_off = status == 0x80 || (status == 0x90 && data[2] == 0);
 
-        process(in_buf_l, in_buf_r, out_buf_l, out_buf_r, &self.params);
+        // a repeat of a held note changes nothing
+        if is_note_on && is_mapped_channel && is_mapped_note {
+            if self.held_notes == 0 && self.params.freeze.get() <= 0.5 {
+                self.is_midi_freeze = true;
+                self.params.freeze.set(1.0);
+            }
+            self.held_notes |= bit;
+        } else if is_note_off && self.held_notes & bit != 0 {
+            self.held_notes &= !bit;
+            if self.held_notes == 0 && self.is_midi_freeze {
+                self.is_midi_freeze = false;
+                self.params.freeze.set(0.0);
+            }
+        }
     }
 }
 
+fn stereo_channel_info((name, short_name): (&str, &str), index: i32) -> ChannelInfo {
+    let (side, channel) = if index == 0 {
+        ("L", StereoChannel::Left)
+    } else {
+        ("R", StereoChannel::Right)
+    };
+    ChannelInfo::new(
+        format!("{} {}", name, side),
+        Some(format!("{} {}", short_name, side)),
+        true,
+        Some(SpeakerArrangementType::Stereo(StereoConfig::L_R, channel)),
+    )
+}
+
 // Boilerplate code, do not modify
 
 struct VstParent(*mut ::std::ffi::c_void);
@@ -152,3 +339,150 @@ unsafe impl HasRawWindowHandle for VstParent {
 }
 
 plugin_main!(Zippify); // Important!
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::param::FREEZE_NOTE_ANY;
+    use vst::host::HostBuffer;
+
+    #[test]
+    fn short_channel_counts_do_not_panic() {
+        let mut plugin = Zippify::new(HostCallback::default());
+        for (inputs, outputs) in [(0, 0), (1, 1), (0, 2), (2, 0), (1, 2), (2, 1)] {
+            let input = vec![vec![0.5f32; 64]; inputs];
+            let mut output = vec![vec![0.5f32; 64]; outputs];
+            let mut host_buffer = HostBuffer::new(inputs, outputs);
+            let mut buffer = host_buffer.bind(&input, &mut output);
+            plugin.process(&mut buffer);
+        }
+    }
+
+    // every output of a fresh plugin given `input` on each of its inputs
+    fn process_with(inputs: usize, outputs: usize, input: &[f32]) -> Vec<Vec<f32>> {
+        let mut plugin = Zippify::new(HostCallback::default());
+        plugin.set_block_size(input.len() as i64);
+        let input = vec![input.to_vec(); inputs];
+        let mut output = vec![vec![0.0f32; input[0].len()]; outputs];
+        let mut host_buffer = HostBuffer::new(inputs, outputs);
+        plugin.process(&mut host_buffer.bind(&input, &mut output));
+        output
+    }
+
+    #[test]
+    fn mono_sides_sound_like_stereo() {
+        let input: Vec<f32> = (0..512)
+            .map(|index| 0.5 * (index as f32 * 0.1).sin())
+            .collect();
+        let stereo = process_with(2, 2, &input);
+        assert!(stereo[0].iter().any(|sample| *sample != 0.0));
+        for (inputs, outputs) in [(1, 1), (1, 2), (2, 1)] {
+            for output in process_with(inputs, outputs, &input) {
+                for (mono, stereo) in output.iter().zip(&stereo[0]) {
+                    assert!(
+                        (mono - stereo).abs() < 1e-6,
+                        "{} in, {} out",
+                        inputs,
+                        outputs
+                    );
+                }
+            }
+        }
+    }
+
+    // the stereo output of a fresh plugin reading from `source`, with `leftover` already
+    // sitting in the output buffers the way some hosts hand them over
+    fn process_from(source: InputSource, input: &[f32], leftover: &[f32]) -> Vec<Vec<f32>> {
+        let mut plugin = Zippify::new(HostCallback::default());
+        plugin.set_block_size(input.len() as i64);
+        plugin.params.input_source.set(source as usize as f32);
+        let input = vec![input.to_vec(); 2];
+        let mut output = vec![leftover.to_vec(); 2];
+        let mut host_buffer = HostBuffer::new(2, 2);
+        plugin.process(&mut host_buffer.bind(&input, &mut output));
+        output
+    }
+
+    #[test]
+    fn each_input_source_reads_its_own_buffer() {
+        let input: Vec<f32> = (0..512)
+            .map(|index| 0.5 * (index as f32 * 0.1).sin())
+            .collect();
+        let leftover: Vec<f32> = (0..512)
+            .map(|index| 0.3 * (index as f32 * 0.37).cos())
+            .collect();
+        let silence = vec![0.0; 512];
+        let from_input = process_with(2, 2, &input);
+        let from_leftover = process_with(2, 2, &leftover);
+        let from_silence = process_with(2, 2, &silence);
+        assert_ne!(from_input, from_leftover);
+        // auto takes the input while there is one and falls back on the output buffer
+        assert_eq!(
+            process_from(InputSource::Auto, &input, &leftover),
+            from_input
+        );
+        assert_eq!(
+            process_from(InputSource::Auto, &silence, &leftover),
+            from_leftover
+        );
+        // the forced ones stick to their buffer, silent or not
+        assert_eq!(
+            process_from(InputSource::Input, &silence, &leftover),
+            from_silence
+        );
+        assert_eq!(
+            process_from(InputSource::Output, &input, &leftover),
+            from_leftover
+        );
+    }
+
+    const NOTE_ON: u8 = 0x90;
+    const NOTE_OFF: u8 = 0x80;
+
+    #[test]
+    fn repeated_notes_hold_the_freeze_until_the_last_one_lets_go() {
+        let mut plugin = Zippify::new(HostCallback::default());
+        plugin.handle_midi([NOTE_ON, 60, 100]);
+        plugin.handle_midi([NOTE_ON, 60, 100]);
+        assert_eq!(plugin.params.freeze.get(), 1.0);
+        // one off is enough for a note however often it came on
+        plugin.handle_midi([NOTE_OFF, 60, 0]);
+        assert_eq!(plugin.params.freeze.get(), 0.0);
+        // and a stray one after that does nothing
+        plugin.handle_midi([NOTE_OFF, 60, 0]);
+        assert_eq!(plugin.params.freeze.get(), 0.0);
+
+        plugin.params.freeze_note.set(FREEZE_NOTE_ANY);
+        plugin.handle_midi([NOTE_ON, 60, 100]);
+        plugin.handle_midi([NOTE_ON, 64, 100]);
+        plugin.handle_midi([NOTE_ON, 60, 0]);
+        assert_eq!(plugin.params.freeze.get(), 1.0);
+        plugin.handle_midi([NOTE_OFF, 64, 0]);
+        assert_eq!(plugin.params.freeze.get(), 0.0);
+    }
+
+    #[test]
+    fn only_the_mapped_note_and_channel_freeze() {
+        let mut plugin = Zippify::new(HostCallback::default());
+        plugin.params.freeze_channel.set(2.0);
+        // wrong note, then the right note on the wrong channel
+        plugin.handle_midi([NOTE_ON | 1, 61, 100]);
+        plugin.handle_midi([NOTE_ON, 60, 100]);
+        assert_eq!(plugin.params.freeze.get(), 0.0);
+        plugin.handle_midi([NOTE_ON | 1, 60, 100]);
+        assert_eq!(plugin.params.freeze.get(), 1.0);
+        // remapping while it's down still lets the note off through
+        plugin.params.freeze_note.set(72.0);
+        plugin.handle_midi([NOTE_OFF | 1, 60, 0]);
+        assert_eq!(plugin.params.freeze.get(), 0.0);
+    }
+
+    #[test]
+    fn notes_leave_a_freeze_from_the_editor_alone() {
+        let mut plugin = Zippify::new(HostCallback::default());
+        plugin.params.freeze.set(1.0);
+        plugin.handle_midi([NOTE_ON, 60, 100]);
+        plugin.handle_midi([NOTE_OFF, 60, 0]);
+        assert_eq!(plugin.params.freeze.get(), 1.0);
+    }
+}
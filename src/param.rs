@@ -1,9 +1,651 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
 use vst::prelude::PluginParameters;
 use vst::util::AtomicFloat;
 
+use crate::analysis::Meters;
 // import functions from util.rs
 use crate::util::{to_db, to_linear};
 
+// Bass mono crossover range in Hz
+pub const MONO_MAKER_FREQ_MIN: f32 = 20.0;
+pub const MONO_MAKER_FREQ_MAX: f32 = 500.0;
+
+// High-pass on the glue's level detector only, off at the bottom of the range
+pub const SIDECHAIN_HPF_MIN: f32 = 20.0;
+pub const SIDECHAIN_HPF_MAX: f32 = 500.0;
+
+// Parallel mode high-passes the wet signal here, so only the added harmonics reach the mix
+pub const WET_HPF_MIN: f32 = 200.0;
+pub const WET_HPF_MAX: f32 = 8000.0;
+const WET_HPF_DEFAULT: f32 = 2000.0;
+
+// Crush sweeps the 8-bitify resolution from clean to destroyed
+pub const CRUSH_BITS_MAX: f32 = 16.0;
+pub const CRUSH_BITS_MIN: f32 = 1.0;
+// 5 bits, the original 15 steps each side
+const CRUSH_DEFAULT: f32 = 11.0 / 15.0;
+
+// Arbitrary level count for the 8-bitify stage, below 2 it follows the crush bits instead
+pub const QUANT_LEVELS_MAX: f32 = 256.0;
+
+// whole levels only, anything below 2 means follow the crush bits
+pub fn snap_levels(levels: f32) -> f32 {
+    let levels = levels.round();
+    if levels < 2.0 {
+        0.0
+    } else {
+        levels
+    }
+}
+
+// Seeds are whole numbers that still fit exactly in the f32 a param is stored in
+pub const SEED_MAX: f32 = 16_777_215.0;
+
+// one whole bit of crush, the param snaps to these
+pub const CRUSH_STEP: f32 = 1.0 / (CRUSH_BITS_MAX - CRUSH_BITS_MIN);
+
+pub fn crush_to_bits(crush: f32) -> f32 {
+    CRUSH_BITS_MAX - crush * (CRUSH_BITS_MAX - CRUSH_BITS_MIN)
+}
+
+// rounds a normalized value to the nearest whole step, for params with discrete values
+pub fn snap(val: f32, step: f32) -> f32 {
+    ((val.clamp(0.0, 1.0) / step).round() * step).min(1.0)
+}
+
+// Input trim reach either way, in dB
+pub const INPUT_TRIM_DB_MAX: f32 = 24.0;
+
+// Emphasis boosts the highs into the clipper by up to this much and cuts them after
+pub const EMPHASIS_DB_MAX: f32 = 12.0;
+
+// How long threshold, gain and mix take to glide to a new value
+pub const SMOOTHING_MS_MAX: f32 = 50.0;
+const SMOOTHING_MS_DEFAULT: f32 = 5.0;
+
+// Amount macro, at the middle the knobs act as set, the top end pulls the threshold
+// down and pushes the gain up by this much
+const AMOUNT_NEUTRAL: f32 = 0.5;
+const AMOUNT_THRESHOLD_DB: f32 = 24.0;
+const AMOUNT_GAIN_DB: f32 = 12.0;
+
+// Glue compressor ranges, times in ms
+pub const GLUE_RATIO_MAX: f32 = 4.0;
+pub const GLUE_ATTACK_MIN: f32 = 1.0;
+pub const GLUE_ATTACK_MAX: f32 = 100.0;
+pub const GLUE_RELEASE_MIN: f32 = 20.0;
+pub const GLUE_RELEASE_MAX: f32 = 1000.0;
+pub const GLUE_HOLD_MAX: f32 = 200.0;
+
+// Clip modes, stored as their index
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ClipMode {
+    Hard,
+    Split,
+    Shave,
+    Curve,
+}
+
+pub const CLIP_MODE_NAMES: [&str; 4] = ["Hard", "Split", "Shave", "Curve"];
+
+impl ClipMode {
+    pub fn from_index(index: f32) -> ClipMode {
+        match index as usize {
+            1 => ClipMode::Split,
+            2 => ClipMode::Shave,
+            3 => ClipMode::Curve,
+            _ => ClipMode::Hard,
+        }
+    }
+}
+
+// Waveshaper curves for the curve clip mode, in table order
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    Soft,
+    Hard,
+    Sigmoid,
+    SineFold,
+    Custom,
+}
+
+pub const CURVE_NAMES: [&str; 5] = ["Soft", "Hard", "S-curve", "Sine fold", "Custom"];
+
+impl Curve {
+    pub fn from_index(index: f32) -> Curve {
+        match index as usize {
+            1 => Curve::Hard,
+            2 => Curve::Sigmoid,
+            3 => Curve::SineFold,
+            4 => Curve::Custom,
+            _ => Curve::Soft,
+        }
+    }
+}
+
+// the custom curve's points, input and output both 0..1, see shaper::CUSTOM_CURVE_RANGE.
+// The first sits at the origin and the last at full input, output never falls going right
+pub const CURVE_POINTS_MAX: usize = 8;
+// closest two points may get along the input
+pub const CURVE_POINT_GAP: f32 = 0.02;
+pub const DEFAULT_CURVE_POINTS: [(f32, f32); 4] =
+    [(0.0, 0.0), (0.2, 0.45), (0.5, 0.85), (1.0, 1.0)];
+
+// puts any set of points into the shape above, so neither the editor nor a preset can
+// hand the shaper a curve that doubles back
+pub fn sanitize_curve_points(points: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let mut sorted: Vec<(f32, f32)> = points
+        .iter()
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .map(|&(x, y)| (x.clamp(0.0, 1.0), y.clamp(0.0, 1.0)))
+        .collect();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let end_y = sorted.last().map_or(1.0, |&(_, y)| y);
+
+    let mut sanitized = vec![(0.0, 0.0)];
+    for &(x, y) in &sorted {
+        let (last_x, last_y) = sanitized[sanitized.len() - 1];
+        if sanitized.len() < CURVE_POINTS_MAX - 1
+            && x >= last_x + CURVE_POINT_GAP
+            && x <= 1.0 - CURVE_POINT_GAP
+        {
+            sanitized.push((x, y.max(last_y)));
+        }
+    }
+    let last_y = sanitized[sanitized.len() - 1].1;
+    sanitized.push((1.0, end_y.max(last_y)));
+    sanitized
+}
+
+// where a point dragged to `(x, y)` ends up, between its neighbours so the curve never
+// falls, the last one stays at full input. Neighbours already at the closest spacing
+// leave no room, float rounding can even cross the bounds, so x then stays put
+pub fn drag_curve_point(points: &[(f32, f32)], index: usize, (x, y): (f32, f32)) -> (f32, f32) {
+    let (prev_x, prev_y) = points[index - 1];
+    let is_last = index == points.len() - 1;
+    let (next_x, next_y) = if is_last {
+        (1.0 + CURVE_POINT_GAP, 1.0)
+    } else {
+        points[index + 1]
+    };
+    let (min_x, max_x) = (prev_x + CURVE_POINT_GAP, next_x - CURVE_POINT_GAP);
+    let x = if is_last {
+        1.0
+    } else if min_x > max_x {
+        points[index].0
+    } else {
+        x.clamp(min_x, max_x)
+    };
+    (x, y.max(prev_y).min(next_y))
+}
+
+// Where process() reads its input from, see Plugin::process
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum InputSource {
+    Auto,
+    Input,
+    Output,
+}
+
+pub const INPUT_SOURCE_NAMES: [&str; 3] = ["Auto", "Input buffer", "Output buffer"];
+
+impl InputSource {
+    pub fn from_index(index: f32) -> InputSource {
+        match index as usize {
+            1 => InputSource::Input,
+            2 => InputSource::Output,
+            _ => InputSource::Auto,
+        }
+    }
+}
+
+// Which input channel feeds which side, applied before anything else
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RoutingMode {
+    Stereo,
+    Swap,
+    LeftOnly,
+    RightOnly,
+}
+
+pub const ROUTING_MODE_NAMES: [&str; 4] = ["Stereo", "Swap L/R", "L to both", "R to both"];
+
+impl RoutingMode {
+    pub fn from_index(index: f32) -> RoutingMode {
+        match index as usize {
+            1 => RoutingMode::Swap,
+            2 => RoutingMode::LeftOnly,
+            3 => RoutingMode::RightOnly,
+            _ => RoutingMode::Stereo,
+        }
+    }
+}
+
+// Where remove_silence sits in the chain
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SilencePosition {
+    // on the clean input, the clipper and the dry signal both see the gated input
+    PreClip,
+    // on the distorted signal after gain
+    PostClip,
+}
+
+pub const SILENCE_POSITION_NAMES: [&str; 2] = ["Before clip", "After gain"];
+
+impl SilencePosition {
+    pub fn from_index(index: f32) -> SilencePosition {
+        match index as usize {
+            0 => SilencePosition::PreClip,
+            _ => SilencePosition::PostClip,
+        }
+    }
+}
+
+// Top of the normal gain range, the reach the gain always had. The host used to map its
+// 0..1 linearly onto a gain of 1 + to_linear(24.0), which tops out at 24.53 dB, and the
+// editor's slider stopped there too
+const GAIN_NORMAL_MAX_DB: f32 = 24.53;
+
+// How far the makeup gain reaches, spread evenly in dB for the host
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GainRange {
+    Subtle,
+    Normal,
+    Slam,
+}
+
+pub const GAIN_RANGE_NAMES: [&str; 3] = ["±12 dB", "+24 dB", "+48 dB"];
+
+impl GainRange {
+    pub fn from_index(index: f32) -> GainRange {
+        match index as usize {
+            0 => GainRange::Subtle,
+            2 => GainRange::Slam,
+            _ => GainRange::Normal,
+        }
+    }
+
+    // (min, max) in dB
+    pub fn db_range(self) -> (f32, f32) {
+        match self {
+            GainRange::Subtle => (-12.0, 12.0),
+            GainRange::Normal => (0.0, GAIN_NORMAL_MAX_DB),
+            GainRange::Slam => (0.0, 48.0),
+        }
+    }
+}
+
+// Where the gain is applied around the clipper
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GainMode {
+    // makeup after the clipper, only the level changes
+    Output,
+    // into the clipper, only how hard it clips changes
+    Drive,
+    // into the clipper and back out again, the character changes at about the same level
+    Linked,
+}
+
+pub const GAIN_MODE_NAMES: [&str; 3] = ["Output", "Drive", "Drive, compensated"];
+
+impl GainMode {
+    pub fn from_index(index: f32) -> GainMode {
+        match index as usize {
+            1 => GainMode::Drive,
+            2 => GainMode::Linked,
+            _ => GainMode::Output,
+        }
+    }
+}
+
+// How the editor's level readouts move, the audio thread always reports raw block levels
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MeterBallistics {
+    // every block as it is
+    Digital,
+    // quick to rise, slow steady fall
+    Ppm,
+    // slow averaging both ways
+    Vu,
+}
+
+pub const METER_BALLISTICS_NAMES: [&str; 3] = ["Digital peak", "PPM", "VU"];
+
+impl MeterBallistics {
+    pub fn from_index(index: f32) -> MeterBallistics {
+        match index as usize {
+            1 => MeterBallistics::Ppm,
+            2 => MeterBallistics::Vu,
+            _ => MeterBallistics::Digital,
+        }
+    }
+}
+
+// Which signals the 8-bitify stage crushes, mid/side keeps the grit centered
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CrushStereo {
+    LeftRight,
+    MidSide,
+    // the side passes clean, so the crushing noise stays in the middle
+    MidOnly,
+}
+
+pub const CRUSH_STEREO_NAMES: [&str; 3] = ["L/R", "Mid/side", "Mid only"];
+
+impl CrushStereo {
+    pub fn from_index(index: f32) -> CrushStereo {
+        match index as usize {
+            1 => CrushStereo::MidSide,
+            2 => CrushStereo::MidOnly,
+            _ => CrushStereo::LeftRight,
+        }
+    }
+}
+
+// Whether the 8-bitify stage crushes the clipped signal or the one going into the clipper
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StageOrder {
+    ClipFirst,
+    CrushFirst,
+}
+
+pub const STAGE_ORDER_NAMES: [&str; 2] = ["Clip, then crush", "Crush, then clip"];
+
+impl StageOrder {
+    pub fn from_index(index: f32) -> StageOrder {
+        match index as usize {
+            1 => StageOrder::CrushFirst,
+            _ => StageOrder::ClipFirst,
+        }
+    }
+}
+
+// Test signal that stands in for the input, for diagnosing without routing audio
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TestSignalMode {
+    Off,
+    Sine,
+    Sweep,
+    Noise,
+}
+
+pub const TEST_SIGNAL_NAMES: [&str; 4] = ["Off", "Sine", "Sweep", "White noise"];
+
+impl TestSignalMode {
+    pub fn from_index(index: f32) -> TestSignalMode {
+        match index as usize {
+            1 => TestSignalMode::Sine,
+            2 => TestSignalMode::Sweep,
+            3 => TestSignalMode::Noise,
+            _ => TestSignalMode::Off,
+        }
+    }
+}
+
+// MIDI note that holds the freeze, below 0 any note does, 60 is C3 as most hosts name it
+pub const FREEZE_NOTE_ANY: f32 = -1.0;
+pub const FREEZE_NOTE_MAX: f32 = 127.0;
+const FREEZE_NOTE_DEFAULT: f32 = 60.0;
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+pub fn freeze_note_text(note: f32) -> String {
+    if note < 0.0 {
+        "Any".to_string()
+    } else {
+        let note = note as usize;
+        format!("{}{}", NOTE_NAMES[note % 12], note as i32 / 12 - 2)
+    }
+}
+
+// MIDI channel the freeze note is taken from, stored as its index so Omni is 0
+pub const FREEZE_CHANNEL_NAMES: [&str; 17] = [
+    "Omni", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16",
+];
+
+// Test sine frequency range in Hz
+pub const TEST_FREQ_MIN: f32 = 20.0;
+pub const TEST_FREQ_MAX: f32 = 20000.0;
+
+// How threshold and gain levels are shown, in the editor and to the host
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LevelUnit {
+    Db,
+    Percent,
+}
+
+pub const LEVEL_UNIT_NAMES: [&str; 2] = ["dBFS", "% of full scale"];
+
+impl LevelUnit {
+    pub fn from_index(index: f32) -> LevelUnit {
+        match index as usize {
+            1 => LevelUnit::Percent,
+            _ => LevelUnit::Db,
+        }
+    }
+
+    pub fn format(self, linear: f32) -> String {
+        match self {
+            LevelUnit::Db => format!("{:.2} dB", to_db(linear)),
+            LevelUnit::Percent => format!("{:.1}%", linear * 100.0),
+        }
+    }
+}
+
+// Trance gate pattern length and step sizes
+pub const GATE_STEPS: usize = 16;
+pub const GATE_RATE_NAMES: [&str; 4] = ["1/4", "1/8", "1/16", "1/32"];
+// length of one step in quarter notes, for each rate
+pub const GATE_RATE_BEATS: [f64; 4] = [1.0, 0.5, 0.25, 0.125];
+
+// step length in beats for a gate rate choice, anything out of range takes the shortest
+pub fn gate_rate_beats(index: f32) -> f64 {
+    GATE_RATE_BEATS[(index as usize).min(GATE_RATE_BEATS.len() - 1)]
+}
+
+// How the gate's gain moves between open and closed
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GateCurve {
+    // eases into the new level, fast at first
+    Exponential,
+    // straight ramp, gets all the way there in the fade time
+    Linear,
+}
+
+pub const GATE_CURVE_NAMES: [&str; 2] = ["Exponential", "Linear"];
+
+impl GateCurve {
+    pub fn from_index(index: f32) -> GateCurve {
+        match index as usize {
+            1 => GateCurve::Linear,
+            _ => GateCurve::Exponential,
+        }
+    }
+}
+
+// the gate steps sit at the end of the regular params
+const GATE_STEP_FIRST: i32 = 17;
+const GATE_STEP_LAST: i32 = GATE_STEP_FIRST + GATE_STEPS as i32 - 1;
+const GATE_STEP_NAMES: [&str; GATE_STEPS] = [
+    "Gate Step 1",
+    "Gate Step 2",
+    "Gate Step 3",
+    "Gate Step 4",
+    "Gate Step 5",
+    "Gate Step 6",
+    "Gate Step 7",
+    "Gate Step 8",
+    "Gate Step 9",
+    "Gate Step 10",
+    "Gate Step 11",
+    "Gate Step 12",
+    "Gate Step 13",
+    "Gate Step 14",
+    "Gate Step 15",
+    "Gate Step 16",
+];
+
+/*
+ * Param metadata, one entry per host index
+ * Values are in the units the field stores, not the normalized host value
+ */
+
+pub struct ParamInfo {
+    pub default: f32,
+    pub min: f32,
+    pub max: f32,
+    // shown after the value, empty when the text carries its own
+    pub unit: &'static str,
+}
+
+impl ParamInfo {
+    // for params spread linearly over their range
+    fn normalize(&self, value: f32) -> f32 {
+        (value - self.min) / (self.max - self.min)
+    }
+
+    fn denormalize(&self, val: f32) -> f32 {
+        self.min + val * (self.max - self.min)
+    }
+}
+
+fn ranged(default: f32, min: f32, max: f32, unit: &'static str) -> ParamInfo {
+    ParamInfo {
+        default,
+        min,
+        max,
+        unit,
+    }
+}
+
+// stored as the 0..1 value the host sends
+fn normalized(default: f32) -> ParamInfo {
+    ranged(default, 0.0, 1.0, "")
+}
+
+// stores the index of the picked name
+fn choice(default: f32, count: usize) -> ParamInfo {
+    ranged(default, 0.0, (count - 1) as f32, "")
+}
+
+pub fn param_info(index: i32) -> ParamInfo {
+    match index {
+        0 => normalized(to_linear(-12.0)),
+        1 => normalized(1.0),
+        2 => normalized(1.0),
+        // the gain range param narrows this down
+        3 => ranged(to_linear(0.0), to_linear(-12.0), to_linear(48.0), ""),
+        4 => normalized(0.0),
+        5 => normalized(0.0),
+        6 => normalized(0.0),
+        7 => normalized(0.0),
+        8 => ranged(120.0, MONO_MAKER_FREQ_MIN, MONO_MAKER_FREQ_MAX, "Hz"),
+        9 => choice(0.0, CLIP_MODE_NAMES.len()),
+        10 => normalized(0.0),
+        11 => normalized(0.0),
+        12 => normalized(1.0),
+        13 => normalized(1.0),
+        14 => normalized(CRUSH_DEFAULT),
+        15 => normalized(0.0),
+        16 => choice(2.0, GATE_RATE_NAMES.len()),
+        // every other 16th open
+        GATE_STEP_FIRST..=GATE_STEP_LAST => normalized(((index - GATE_STEP_FIRST + 1) % 2) as f32),
+        33 => choice(0.0, INPUT_SOURCE_NAMES.len()),
+        34 => normalized(0.0),
+        35 => choice(0.0, ROUTING_MODE_NAMES.len()),
+        36 => normalized(0.0),
+        37 => normalized(0.0),
+        38 => normalized(0.0),
+        39 => choice(0.0, CLIP_MODE_NAMES.len()),
+        40 => normalized(to_linear(-6.0)),
+        41 => normalized(0.0),
+        42 => normalized(0.0),
+        43 => normalized(to_linear(-18.0)),
+        44 => ranged(2.0, 1.0, GLUE_RATIO_MAX, ":1"),
+        45 => ranged(30.0, GLUE_ATTACK_MIN, GLUE_ATTACK_MAX, "ms"),
+        46 => ranged(200.0, GLUE_RELEASE_MIN, GLUE_RELEASE_MAX, "ms"),
+        47 => ranged(SMOOTHING_MS_DEFAULT, 0.0, SMOOTHING_MS_MAX, "ms"),
+        48 => choice(0.0, CURVE_NAMES.len()),
+        49 => ranged(0.0, 0.0, QUANT_LEVELS_MAX, "levels"),
+        50 => normalized(0.0),
+        51 => ranged(0.0, 0.0, SEED_MAX, ""),
+        52 => normalized(0.0),
+        53 => choice(0.0, LEVEL_UNIT_NAMES.len()),
+        54 => choice(1.0, SILENCE_POSITION_NAMES.len()),
+        55 => ranged(SMOOTHING_MS_DEFAULT, 0.0, SMOOTHING_MS_MAX, "ms"),
+        56 => normalized(0.0),
+        57 => normalized(0.0),
+        58 => normalized(0.0),
+        59 => choice(1.0, GAIN_RANGE_NAMES.len()),
+        60 => normalized(1.0),
+        61 => normalized(0.0),
+        62 => normalized(0.0),
+        63 => normalized(0.0),
+        64 => ranged(
+            SIDECHAIN_HPF_MIN,
+            SIDECHAIN_HPF_MIN,
+            SIDECHAIN_HPF_MAX,
+            "Hz",
+        ),
+        65 => normalized(1.0),
+        66 => normalized(AMOUNT_NEUTRAL),
+        67 => normalized(0.0),
+        68 => ranged(WET_HPF_DEFAULT, WET_HPF_MIN, WET_HPF_MAX, "Hz"),
+        69 => choice(0.0, TEST_SIGNAL_NAMES.len()),
+        70 => ranged(1000.0, TEST_FREQ_MIN, TEST_FREQ_MAX, "Hz"),
+        71 => normalized(0.0),
+        72 => choice(0.0, STAGE_ORDER_NAMES.len()),
+        73 => choice(0.0, GATE_CURVE_NAMES.len()),
+        74 => normalized(0.0),
+        75 => normalized(0.0),
+        76 => choice(0.0, GAIN_MODE_NAMES.len()),
+        77 => normalized(0.0),
+        78 => choice(0.0, METER_BALLISTICS_NAMES.len()),
+        79 => ranged(0.0, -INPUT_TRIM_DB_MAX, INPUT_TRIM_DB_MAX, "dB"),
+        80 => choice(0.0, CRUSH_STEREO_NAMES.len()),
+        81 => ranged(0.0, 0.0, GLUE_HOLD_MAX, "ms"),
+        82 => ranged(FREEZE_NOTE_DEFAULT, FREEZE_NOTE_ANY, FREEZE_NOTE_MAX, ""),
+        83 => choice(0.0, FREEZE_CHANNEL_NAMES.len()),
+        84 => normalized(1.0),
+        85 => normalized(0.0),
+        86 => normalized(0.0),
+        87 => normalized(0.0),
+        _ => normalized(0.0),
+    }
+}
+
+// choice params store their index, hosts see it spread over 0..1
+fn choice_to_normalized(index: f32, count: usize) -> f32 {
+    index / (count - 1) as f32
+}
+
+fn normalized_to_choice(val: f32, count: usize) -> f32 {
+    (val.clamp(0.0, 1.0) * (count - 1) as f32).round()
+}
+
+// log scale between min and max, for frequencies and times
+fn log_to_normalized(value: f32, min: f32, max: f32) -> f32 {
+    (value / min).ln() / (max / min).ln()
+}
+
+fn normalized_to_log(val: f32, min: f32, max: f32) -> f32 {
+    min * (max / min).powf(val)
+}
+
+// linear level spread evenly in dB between min_db and max_db, silence and anything
+// outside the range land on the nearest end so the host never sees less than 0 or more than 1
+fn level_to_normalized(linear: f32, min_db: f32, max_db: f32) -> f32 {
+    ((to_db(linear) - min_db) / (max_db - min_db)).clamp(0.0, 1.0)
+}
+
+// the exact inverse of level_to_normalized for anything inside the range
+fn normalized_to_level(val: f32, min_db: f32, max_db: f32) -> f32 {
+    to_linear(min_db + val.clamp(0.0, 1.0) * (max_db - min_db))
+}
+
 /*
  * Declare and impl params
  * Use atomic types for thread safety
@@ -14,21 +656,464 @@ pub struct EffectParams {
     pub lose_precision: AtomicFloat,
     pub mix: AtomicFloat,
     pub gain: AtomicFloat,
+    pub invert_l: AtomicFloat,
+    pub invert_r: AtomicFloat,
+    pub output_ceiling: AtomicFloat,
+    pub mono_maker: AtomicFloat,
+    pub mono_maker_freq: AtomicFloat,
+    pub clip_mode: AtomicFloat,
+    pub output_dither: AtomicFloat,
+    pub ms_clip: AtomicFloat,
+    pub mid_threshold: AtomicFloat,
+    pub side_threshold: AtomicFloat,
+    pub crush: AtomicFloat,
+    pub gate: AtomicFloat,
+    pub gate_rate: AtomicFloat,
+    pub gate_steps: [AtomicFloat; GATE_STEPS],
+    pub input_source: AtomicFloat,
+    pub tape: AtomicFloat,
+    pub routing_mode: AtomicFloat,
+    pub emphasis: AtomicFloat,
+    pub freeze: AtomicFloat,
+    pub clip2: AtomicFloat,
+    pub clip2_mode: AtomicFloat,
+    pub clip2_threshold: AtomicFloat,
+    pub dry_bleed: AtomicFloat,
+    pub glue: AtomicFloat,
+    pub glue_threshold: AtomicFloat,
+    pub glue_ratio: AtomicFloat,
+    pub glue_attack: AtomicFloat,
+    pub glue_release: AtomicFloat,
+    pub smoothing_ms: AtomicFloat,
+    pub curve: AtomicFloat,
+    pub quant_levels: AtomicFloat,
+    pub mu_law: AtomicFloat,
+    pub seed: AtomicFloat,
+    pub compact: AtomicFloat,
+    pub level_unit: AtomicFloat,
+    pub silence_position: AtomicFloat,
+    pub threshold_smoothing_ms: AtomicFloat,
+    pub safety_lpf: AtomicFloat,
+    pub legacy_silence: AtomicFloat,
+    pub transient: AtomicFloat,
+    pub gain_range: AtomicFloat,
+    pub side_image: AtomicFloat,
+    pub wow: AtomicFloat,
+    pub flutter: AtomicFloat,
+    pub silence_link: AtomicFloat,
+    pub sidechain_hpf: AtomicFloat,
+    pub true_peak_meter: AtomicFloat,
+    pub amount: AtomicFloat,
+    pub parallel: AtomicFloat,
+    pub wet_hpf: AtomicFloat,
+    pub test_signal: AtomicFloat,
+    pub test_freq: AtomicFloat,
+    pub analog_drift: AtomicFloat,
+    pub stage_order: AtomicFloat,
+    pub gate_curve: AtomicFloat,
+    pub dry_match: AtomicFloat,
+    pub lite_ui: AtomicFloat,
+    pub gain_mode: AtomicFloat,
+    pub sat_floor: AtomicFloat,
+    pub meter_ballistics: AtomicFloat,
+    pub input_trim: AtomicFloat,
+    pub crush_stereo: AtomicFloat,
+    pub glue_hold: AtomicFloat,
+    pub freeze_note: AtomicFloat,
+    pub freeze_channel: AtomicFloat,
+    pub power_save: AtomicFloat,
+    pub side_limit: AtomicFloat,
+    pub compare: AtomicFloat,
+    pub level_match: AtomicFloat,
+    // not exposed to the host
+    pub meters: Meters,
+    // held from the editor, folds the output to mono for checking compatibility
+    pub check_mono: AtomicBool,
+    // toggled in the editor, the split clip mode only lets the soloed bands through
+    pub solo_low: AtomicBool,
+    pub solo_high: AtomicBool,
+    // set by the editor, the next block restarts the gate pattern from step 1
+    pub gate_reset: AtomicBool,
+    // set by the editor's panic button, the next block clears all DSP state and fades in
+    pub panic: AtomicBool,
+    // free text naming this instance, saved with the preset chunk
+    pub label: Mutex<String>,
+    // points of the custom curve, set through set_curve_points, saved with the preset chunk
+    pub curve_points: Mutex<Vec<(f32, f32)>>,
+    // set when the points change, the next block rebuilds the custom curve's table
+    pub curve_changed: AtomicBool,
 }
 
-pub const PARAM_NUM: i32 = 4;
+pub const PARAM_NUM: i32 = 88;
+
+// preset chunk layout, all little endian:
+// magic, version, param count, normalized params, label length, label utf-8
+const CHUNK_MAGIC: &[u8; 4] = b"ZPFY";
+// 2 added the custom curve's points after the label
+const CHUNK_VERSION: u32 = 2;
+pub const LABEL_MAX_BYTES: usize = 64;
+
+fn param_default(index: i32) -> AtomicFloat {
+    AtomicFloat::new(param_info(index).default)
+}
 
 impl Default for EffectParams {
     fn default() -> EffectParams {
         EffectParams {
-            clamp_threshold: AtomicFloat::new(to_linear(-12.0)),
-            lose_precision: AtomicFloat::new(1.0),
-            mix: AtomicFloat::new(1.0),
-            gain: AtomicFloat::new(to_linear(0.0)),
+            clamp_threshold: param_default(0),
+            lose_precision: param_default(1),
+            mix: param_default(2),
+            gain: param_default(3),
+            invert_l: param_default(4),
+            invert_r: param_default(5),
+            output_ceiling: param_default(6),
+            mono_maker: param_default(7),
+            mono_maker_freq: param_default(8),
+            clip_mode: param_default(9),
+            output_dither: param_default(10),
+            ms_clip: param_default(11),
+            mid_threshold: param_default(12),
+            side_threshold: param_default(13),
+            crush: param_default(14),
+            gate: param_default(15),
+            gate_rate: param_default(16),
+            gate_steps: std::array::from_fn(|step| param_default(GATE_STEP_FIRST + step as i32)),
+            input_source: param_default(33),
+            tape: param_default(34),
+            routing_mode: param_default(35),
+            emphasis: param_default(36),
+            freeze: param_default(37),
+            clip2: param_default(38),
+            clip2_mode: param_default(39),
+            clip2_threshold: param_default(40),
+            dry_bleed: param_default(41),
+            glue: param_default(42),
+            glue_threshold: param_default(43),
+            glue_ratio: param_default(44),
+            glue_attack: param_default(45),
+            glue_release: param_default(46),
+            smoothing_ms: param_default(47),
+            curve: param_default(48),
+            quant_levels: param_default(49),
+            mu_law: param_default(50),
+            seed: param_default(51),
+            compact: param_default(52),
+            level_unit: param_default(53),
+            silence_position: param_default(54),
+            threshold_smoothing_ms: param_default(55),
+            safety_lpf: param_default(56),
+            legacy_silence: param_default(57),
+            transient: param_default(58),
+            gain_range: param_default(59),
+            side_image: param_default(60),
+            wow: param_default(61),
+            flutter: param_default(62),
+            silence_link: param_default(63),
+            sidechain_hpf: param_default(64),
+            true_peak_meter: param_default(65),
+            amount: param_default(66),
+            parallel: param_default(67),
+            wet_hpf: param_default(68),
+            test_signal: param_default(69),
+            test_freq: param_default(70),
+            analog_drift: param_default(71),
+            stage_order: param_default(72),
+            gate_curve: param_default(73),
+            dry_match: param_default(74),
+            lite_ui: param_default(75),
+            gain_mode: param_default(76),
+            sat_floor: param_default(77),
+            meter_ballistics: param_default(78),
+            input_trim: param_default(79),
+            crush_stereo: param_default(80),
+            glue_hold: param_default(81),
+            freeze_note: param_default(82),
+            freeze_channel: param_default(83),
+            power_save: param_default(84),
+            side_limit: param_default(85),
+            compare: param_default(86),
+            level_match: param_default(87),
+            meters: Meters::default(),
+            check_mono: AtomicBool::new(false),
+            solo_low: AtomicBool::new(false),
+            solo_high: AtomicBool::new(false),
+            gate_reset: AtomicBool::new(false),
+            panic: AtomicBool::new(false),
+            label: Mutex::new(String::new()),
+            curve_points: Mutex::new(DEFAULT_CURVE_POINTS.to_vec()),
+            curve_changed: AtomicBool::new(false),
         }
     }
 }
 
+impl EffectParams {
+    pub fn gain_db_range(&self) -> (f32, f32) {
+        GainRange::from_index(self.gain_range.get()).db_range()
+    }
+
+    // after a range switch the gain keeps its level, only pulled in when it no longer fits
+    pub fn fit_gain_to_range(&self) {
+        let (min_db, max_db) = self.gain_db_range();
+        self.gain
+            .set(to_linear(to_db(self.gain.get()).clamp(min_db, max_db)));
+    }
+
+    // threshold, gain and mix as bent by the amount macro around their own knobs,
+    // below the middle everything eases off towards a clean signal, above it leans in
+    pub fn amount_shaped(&self) -> (f32, f32, f32) {
+        let (threshold, gain, mix) = (self.clamp_threshold.get(), self.gain.get(), self.mix.get());
+        let amount = self.amount.get();
+        if amount < AMOUNT_NEUTRAL {
+            let scale = amount / AMOUNT_NEUTRAL;
+            (threshold.powf(scale), gain.powf(scale), mix * scale)
+        } else {
+            let push = (amount - AMOUNT_NEUTRAL) / (1.0 - AMOUNT_NEUTRAL);
+            let (_, max_db) = self.gain_db_range();
+            (
+                threshold * to_linear(-AMOUNT_THRESHOLD_DB * push),
+                (gain * to_linear(AMOUNT_GAIN_DB * push)).min(to_linear(max_db)),
+                mix + (1.0 - mix) * push,
+            )
+        }
+    }
+
+    // a linear level in the unit picked by the level unit param
+    pub fn format_level(&self, linear: f32) -> String {
+        LevelUnit::from_index(self.level_unit.get()).format(linear)
+    }
+
+    // the field behind a host param, note it holds the param's own scale, not 0..1
+    pub fn param_at(&self, index: i32) -> Option<&AtomicFloat> {
+        match index {
+            0 => Some(&self.clamp_threshold),
+            1 => Some(&self.lose_precision),
+            2 => Some(&self.mix),
+            3 => Some(&self.gain),
+            4 => Some(&self.invert_l),
+            5 => Some(&self.invert_r),
+            6 => Some(&self.output_ceiling),
+            7 => Some(&self.mono_maker),
+            8 => Some(&self.mono_maker_freq),
+            9 => Some(&self.clip_mode),
+            10 => Some(&self.output_dither),
+            11 => Some(&self.ms_clip),
+            12 => Some(&self.mid_threshold),
+            13 => Some(&self.side_threshold),
+            14 => Some(&self.crush),
+            15 => Some(&self.gate),
+            16 => Some(&self.gate_rate),
+            GATE_STEP_FIRST..=GATE_STEP_LAST => {
+                Some(&self.gate_steps[(index - GATE_STEP_FIRST) as usize])
+            }
+            33 => Some(&self.input_source),
+            34 => Some(&self.tape),
+            35 => Some(&self.routing_mode),
+            36 => Some(&self.emphasis),
+            37 => Some(&self.freeze),
+            38 => Some(&self.clip2),
+            39 => Some(&self.clip2_mode),
+            40 => Some(&self.clip2_threshold),
+            41 => Some(&self.dry_bleed),
+            42 => Some(&self.glue),
+            43 => Some(&self.glue_threshold),
+            44 => Some(&self.glue_ratio),
+            45 => Some(&self.glue_attack),
+            46 => Some(&self.glue_release),
+            47 => Some(&self.smoothing_ms),
+            48 => Some(&self.curve),
+            49 => Some(&self.quant_levels),
+            50 => Some(&self.mu_law),
+            51 => Some(&self.seed),
+            52 => Some(&self.compact),
+            53 => Some(&self.level_unit),
+            54 => Some(&self.silence_position),
+            55 => Some(&self.threshold_smoothing_ms),
+            56 => Some(&self.safety_lpf),
+            57 => Some(&self.legacy_silence),
+            58 => Some(&self.transient),
+            59 => Some(&self.gain_range),
+            60 => Some(&self.side_image),
+            61 => Some(&self.wow),
+            62 => Some(&self.flutter),
+            63 => Some(&self.silence_link),
+            64 => Some(&self.sidechain_hpf),
+            65 => Some(&self.true_peak_meter),
+            66 => Some(&self.amount),
+            67 => Some(&self.parallel),
+            68 => Some(&self.wet_hpf),
+            69 => Some(&self.test_signal),
+            70 => Some(&self.test_freq),
+            71 => Some(&self.analog_drift),
+            72 => Some(&self.stage_order),
+            73 => Some(&self.gate_curve),
+            74 => Some(&self.dry_match),
+            75 => Some(&self.lite_ui),
+            76 => Some(&self.gain_mode),
+            77 => Some(&self.sat_floor),
+            78 => Some(&self.meter_ballistics),
+            79 => Some(&self.input_trim),
+            80 => Some(&self.crush_stereo),
+            81 => Some(&self.glue_hold),
+            82 => Some(&self.freeze_note),
+            83 => Some(&self.freeze_channel),
+            84 => Some(&self.power_save),
+            85 => Some(&self.side_limit),
+            86 => Some(&self.compare),
+            87 => Some(&self.level_match),
+            _ => None,
+        }
+    }
+
+    // the 0..1 value an automation lane holds for the param, next to how the host shows it
+    pub fn host_value_text(&self, index: i32) -> String {
+        format!(
+            "Host value {:.4}, {}",
+            self.get_parameter(index),
+            self.get_parameter_text(index)
+        )
+    }
+
+    pub fn label(&self) -> String {
+        self.label
+            .lock()
+            .map(|label| label.clone())
+            .unwrap_or_default()
+    }
+
+    // cut at a char boundary so the label always fits its chunk slot
+    pub fn set_label(&self, text: &str) {
+        let mut end = text.len().min(LABEL_MAX_BYTES);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        if let Ok(mut label) = self.label.lock() {
+            *label = text[..end].to_string();
+        }
+    }
+
+    pub fn curve_points(&self) -> Vec<(f32, f32)> {
+        self.curve_points
+            .lock()
+            .map(|points| points.clone())
+            .unwrap_or_else(|_| DEFAULT_CURVE_POINTS.to_vec())
+    }
+
+    pub fn set_curve_points(&self, points: &[(f32, f32)]) {
+        if let Ok(mut curve_points) = self.curve_points.lock() {
+            *curve_points = sanitize_curve_points(points);
+        }
+        self.curve_changed.store(true, Ordering::Relaxed);
+    }
+
+    fn to_chunk(&self) -> Vec<u8> {
+        let label = self.label();
+        let points = self.curve_points();
+        let mut data =
+            Vec::with_capacity(20 + PARAM_NUM as usize * 4 + label.len() + points.len() * 8);
+        data.extend_from_slice(CHUNK_MAGIC);
+        data.extend_from_slice(&CHUNK_VERSION.to_le_bytes());
+        data.extend_from_slice(&(PARAM_NUM as u32).to_le_bytes());
+        for index in 0..PARAM_NUM {
+            // freeze is a held button, a project saved mid-hold shouldn't reopen frozen
+            let val = if index == 37 {
+                0.0
+            } else {
+                self.get_parameter(index)
+            };
+            data.extend_from_slice(&val.to_le_bytes());
+        }
+        data.extend_from_slice(&(label.len() as u32).to_le_bytes());
+        data.extend_from_slice(label.as_bytes());
+        data.extend_from_slice(&(points.len() as u32).to_le_bytes());
+        for (x, y) in points {
+            data.extend_from_slice(&x.to_le_bytes());
+            data.extend_from_slice(&y.to_le_bytes());
+        }
+        data
+    }
+
+    // hosts hand back whatever they stored, anything that doesn't parse is left alone
+    fn load_chunk(&self, data: &[u8]) {
+        let mut reader = ChunkReader { data };
+        if reader.take(4) != Some(&CHUNK_MAGIC[..]) {
+            return;
+        }
+        let version = match reader.u32() {
+            Some(version) if (1..=CHUNK_VERSION).contains(&version) => version,
+            _ => return,
+        };
+        let values = match reader.u32().and_then(|count| reader.f32s(count as usize)) {
+            Some(values) => values,
+            None => return,
+        };
+        // the gain is stored relative to its range, so the range goes first
+        if let Some(&range) = values.get(59) {
+            self.set_parameter(59, range);
+        }
+        for (index, &val) in values.iter().enumerate().take(PARAM_NUM as usize) {
+            if index != 59 && index != 37 {
+                self.set_parameter(index as i32, val);
+            }
+        }
+        // older chunks may still hold a freeze
+        self.freeze.set(0.0);
+        // a damaged label only costs the label
+        let label = reader
+            .u32()
+            .filter(|&len| len as usize <= LABEL_MAX_BYTES)
+            .and_then(|len| reader.take(len as usize))
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .unwrap_or("");
+        self.set_label(label);
+        // older chunks have no curve and damaged points fall back to the default one
+        let points = if version >= 2 {
+            reader
+                .u32()
+                .filter(|&count| count as usize <= CURVE_POINTS_MAX)
+                .and_then(|count| reader.f32s(count as usize * 2))
+                .map(|values| {
+                    values
+                        .chunks_exact(2)
+                        .map(|point| (point[0], point[1]))
+                        .collect::<Vec<(f32, f32)>>()
+                })
+        } else {
+            None
+        };
+        self.set_curve_points(&points.unwrap_or_else(|| DEFAULT_CURVE_POINTS.to_vec()));
+    }
+}
+
+struct ChunkReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ChunkReader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.data.len() {
+            return None;
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(head)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    // the count is checked against what's left before anything gets allocated for it
+    fn f32s(&mut self, count: usize) -> Option<Vec<f32>> {
+        self.take(count.saturating_mul(4)).map(|bytes| {
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()
+        })
+    }
+}
+
 impl PluginParameters for EffectParams {
     // getter
     fn get_parameter(&self, index: i32) -> f32 {
@@ -37,34 +1122,331 @@ impl PluginParameters for EffectParams {
             1 => self.lose_precision.get(),
             2 => self.mix.get(),
             3 => {
-                let gain = self.gain.get();
-                (gain - 1.0) / to_linear(24.0)
+                let (min_db, max_db) = self.gain_db_range();
+                level_to_normalized(self.gain.get(), min_db, max_db)
+            }
+            4 => self.invert_l.get(),
+            5 => self.invert_r.get(),
+            6 => self.output_ceiling.get(),
+            7 => self.mono_maker.get(),
+            8 => log_to_normalized(
+                self.mono_maker_freq.get(),
+                MONO_MAKER_FREQ_MIN,
+                MONO_MAKER_FREQ_MAX,
+            ),
+            9 => choice_to_normalized(self.clip_mode.get(), CLIP_MODE_NAMES.len()),
+            10 => self.output_dither.get(),
+            11 => self.ms_clip.get(),
+            12 => self.mid_threshold.get(),
+            13 => self.side_threshold.get(),
+            14 => self.crush.get(),
+            15 => self.gate.get(),
+            16 => choice_to_normalized(self.gate_rate.get(), GATE_RATE_NAMES.len()),
+            GATE_STEP_FIRST..=GATE_STEP_LAST => {
+                self.gate_steps[(index - GATE_STEP_FIRST) as usize].get()
             }
+            33 => choice_to_normalized(self.input_source.get(), INPUT_SOURCE_NAMES.len()),
+            34 => self.tape.get(),
+            35 => choice_to_normalized(self.routing_mode.get(), ROUTING_MODE_NAMES.len()),
+            36 => self.emphasis.get(),
+            37 => self.freeze.get(),
+            38 => self.clip2.get(),
+            39 => choice_to_normalized(self.clip2_mode.get(), CLIP_MODE_NAMES.len()),
+            40 => self.clip2_threshold.get(),
+            41 => self.dry_bleed.get(),
+            42 => self.glue.get(),
+            43 => self.glue_threshold.get(),
+            44 => param_info(index).normalize(self.glue_ratio.get()),
+            45 => log_to_normalized(self.glue_attack.get(), GLUE_ATTACK_MIN, GLUE_ATTACK_MAX),
+            46 => log_to_normalized(self.glue_release.get(), GLUE_RELEASE_MIN, GLUE_RELEASE_MAX),
+            47 => param_info(index).normalize(self.smoothing_ms.get()),
+            48 => choice_to_normalized(self.curve.get(), CURVE_NAMES.len()),
+            49 => self.quant_levels.get() / QUANT_LEVELS_MAX,
+            50 => self.mu_law.get(),
+            51 => self.seed.get() / SEED_MAX,
+            52 => self.compact.get(),
+            53 => choice_to_normalized(self.level_unit.get(), LEVEL_UNIT_NAMES.len()),
+            54 => choice_to_normalized(self.silence_position.get(), SILENCE_POSITION_NAMES.len()),
+            55 => param_info(index).normalize(self.threshold_smoothing_ms.get()),
+            56 => self.safety_lpf.get(),
+            57 => self.legacy_silence.get(),
+            58 => self.transient.get(),
+            59 => choice_to_normalized(self.gain_range.get(), GAIN_RANGE_NAMES.len()),
+            60 => self.side_image.get(),
+            61 => self.wow.get(),
+            62 => self.flutter.get(),
+            63 => self.silence_link.get(),
+            64 => log_to_normalized(
+                self.sidechain_hpf.get(),
+                SIDECHAIN_HPF_MIN,
+                SIDECHAIN_HPF_MAX,
+            ),
+            65 => self.true_peak_meter.get(),
+            66 => self.amount.get(),
+            67 => self.parallel.get(),
+            68 => log_to_normalized(self.wet_hpf.get(), WET_HPF_MIN, WET_HPF_MAX),
+            69 => choice_to_normalized(self.test_signal.get(), TEST_SIGNAL_NAMES.len()),
+            70 => log_to_normalized(self.test_freq.get(), TEST_FREQ_MIN, TEST_FREQ_MAX),
+            71 => self.analog_drift.get(),
+            72 => choice_to_normalized(self.stage_order.get(), STAGE_ORDER_NAMES.len()),
+            73 => choice_to_normalized(self.gate_curve.get(), GATE_CURVE_NAMES.len()),
+            74 => self.dry_match.get(),
+            75 => self.lite_ui.get(),
+            76 => choice_to_normalized(self.gain_mode.get(), GAIN_MODE_NAMES.len()),
+            77 => self.sat_floor.get(),
+            78 => choice_to_normalized(self.meter_ballistics.get(), METER_BALLISTICS_NAMES.len()),
+            79 => param_info(index).normalize(self.input_trim.get()),
+            80 => choice_to_normalized(self.crush_stereo.get(), CRUSH_STEREO_NAMES.len()),
+            81 => param_info(index).normalize(self.glue_hold.get()),
+            82 => param_info(index).normalize(self.freeze_note.get()),
+            83 => choice_to_normalized(self.freeze_channel.get(), FREEZE_CHANNEL_NAMES.len()),
+            84 => self.power_save.get(),
+            85 => self.side_limit.get(),
+            86 => self.compare.get(),
+            87 => self.level_match.get(),
             _ => 0.0,
         }
     }
 
     // setter
     fn set_parameter(&self, index: i32, val: f32) {
+        // presets are restored through here too, keep garbage away from the DSP,
+        // the default is already in the field's own units
+        if !val.is_finite() {
+            if let Some(param) = self.param_at(index) {
+                param.set(param_info(index).default);
+            }
+            self.fit_gain_to_range();
+            return;
+        }
+        let val = val.clamp(0.0, 1.0);
+
         match index {
             0 => self.clamp_threshold.set(val),
             1 => self.lose_precision.set(val),
             2 => self.mix.set(val),
             3 => {
-                let gain = val * to_linear(24.0) + 1.0;
-                self.gain.set(gain);
+                let (min_db, max_db) = self.gain_db_range();
+                self.gain.set(normalized_to_level(val, min_db, max_db));
+            }
+            4 => self.invert_l.set(val),
+            5 => self.invert_r.set(val),
+            6 => self.output_ceiling.set(val),
+            7 => self.mono_maker.set(val),
+            8 => self.mono_maker_freq.set(normalized_to_log(
+                val,
+                MONO_MAKER_FREQ_MIN,
+                MONO_MAKER_FREQ_MAX,
+            )),
+            9 => self
+                .clip_mode
+                .set(normalized_to_choice(val, CLIP_MODE_NAMES.len())),
+            10 => self.output_dither.set(val),
+            11 => self.ms_clip.set(val),
+            12 => self.mid_threshold.set(val),
+            13 => self.side_threshold.set(val),
+            14 => self.crush.set(snap(val, CRUSH_STEP)),
+            15 => self.gate.set(val),
+            16 => self
+                .gate_rate
+                .set(normalized_to_choice(val, GATE_RATE_NAMES.len())),
+            GATE_STEP_FIRST..=GATE_STEP_LAST => {
+                self.gate_steps[(index - GATE_STEP_FIRST) as usize].set(val)
             }
+            33 => self
+                .input_source
+                .set(normalized_to_choice(val, INPUT_SOURCE_NAMES.len())),
+            34 => self.tape.set(val),
+            35 => self
+                .routing_mode
+                .set(normalized_to_choice(val, ROUTING_MODE_NAMES.len())),
+            36 => self.emphasis.set(val),
+            37 => self.freeze.set(val),
+            38 => self.clip2.set(val),
+            39 => self
+                .clip2_mode
+                .set(normalized_to_choice(val, CLIP_MODE_NAMES.len())),
+            40 => self.clip2_threshold.set(val),
+            41 => self.dry_bleed.set(val),
+            42 => self.glue.set(val),
+            43 => self.glue_threshold.set(val),
+            44 => self.glue_ratio.set(param_info(index).denormalize(val)),
+            45 => self
+                .glue_attack
+                .set(normalized_to_log(val, GLUE_ATTACK_MIN, GLUE_ATTACK_MAX)),
+            46 => self
+                .glue_release
+                .set(normalized_to_log(val, GLUE_RELEASE_MIN, GLUE_RELEASE_MAX)),
+            47 => self.smoothing_ms.set(param_info(index).denormalize(val)),
+            48 => self.curve.set(normalized_to_choice(val, CURVE_NAMES.len())),
+            49 => self.quant_levels.set(snap_levels(val * QUANT_LEVELS_MAX)),
+            50 => self.mu_law.set(val),
+            51 => self.seed.set((val * SEED_MAX).round()),
+            52 => self.compact.set(val),
+            53 => self
+                .level_unit
+                .set(normalized_to_choice(val, LEVEL_UNIT_NAMES.len())),
+            54 => self
+                .silence_position
+                .set(normalized_to_choice(val, SILENCE_POSITION_NAMES.len())),
+            55 => self
+                .threshold_smoothing_ms
+                .set(param_info(index).denormalize(val)),
+            56 => self.safety_lpf.set(val),
+            57 => self.legacy_silence.set(val),
+            58 => self.transient.set(val),
+            59 => {
+                self.gain_range
+                    .set(normalized_to_choice(val, GAIN_RANGE_NAMES.len()));
+                self.fit_gain_to_range();
+            }
+            60 => self.side_image.set(val),
+            61 => self.wow.set(val),
+            62 => self.flutter.set(val),
+            63 => self.silence_link.set(val),
+            64 => {
+                self.sidechain_hpf
+                    .set(normalized_to_log(val, SIDECHAIN_HPF_MIN, SIDECHAIN_HPF_MAX))
+            }
+            65 => self.true_peak_meter.set(val),
+            66 => self.amount.set(val),
+            67 => self.parallel.set(val),
+            68 => self
+                .wet_hpf
+                .set(normalized_to_log(val, WET_HPF_MIN, WET_HPF_MAX)),
+            69 => self
+                .test_signal
+                .set(normalized_to_choice(val, TEST_SIGNAL_NAMES.len())),
+            70 => self
+                .test_freq
+                .set(normalized_to_log(val, TEST_FREQ_MIN, TEST_FREQ_MAX)),
+            71 => self.analog_drift.set(val),
+            72 => self
+                .stage_order
+                .set(normalized_to_choice(val, STAGE_ORDER_NAMES.len())),
+            73 => self
+                .gate_curve
+                .set(normalized_to_choice(val, GATE_CURVE_NAMES.len())),
+            74 => self.dry_match.set(val),
+            75 => self.lite_ui.set(val),
+            76 => self
+                .gain_mode
+                .set(normalized_to_choice(val, GAIN_MODE_NAMES.len())),
+            77 => self.sat_floor.set(val),
+            78 => self
+                .meter_ballistics
+                .set(normalized_to_choice(val, METER_BALLISTICS_NAMES.len())),
+            79 => self.input_trim.set(param_info(index).denormalize(val)),
+            80 => self
+                .crush_stereo
+                .set(normalized_to_choice(val, CRUSH_STEREO_NAMES.len())),
+            81 => self.glue_hold.set(param_info(index).denormalize(val)),
+            82 => self
+                .freeze_note
+                .set(param_info(index).denormalize(val).round()),
+            83 => self
+                .freeze_channel
+                .set(normalized_to_choice(val, FREEZE_CHANNEL_NAMES.len())),
+            84 => self.power_save.set(val),
+            85 => self.side_limit.set(val),
+            86 => self.compare.set(val),
+            87 => self.level_match.set(val),
             _ => (),
         }
     }
 
     // shows formatted param
     fn get_parameter_text(&self, index: i32) -> String {
+        let unit = param_info(index).unit;
         match index {
-            0 => format!("{:.2} dB", to_db(self.clamp_threshold.get())),
+            0 => self.format_level(self.clamp_threshold.get()),
             1 => format!("{:.2}", self.lose_precision.get()),
             2 => format!("{:.2}", self.mix.get()),
-            3 => format!("{:.2} dB", to_db(self.gain.get())),
+            3 => self.format_level(self.gain.get()),
+            4 => format!("{:.2}", self.invert_l.get()),
+            5 => format!("{:.2}", self.invert_r.get()),
+            6 => format!("{:.2}", self.output_ceiling.get()),
+            7 => format!("{:.2}", self.mono_maker.get()),
+            8 => format!("{:.0} {}", self.mono_maker_freq.get(), unit),
+            9 => CLIP_MODE_NAMES[self.clip_mode.get() as usize].to_string(),
+            10 => format!("{:.2}", self.output_dither.get()),
+            11 => format!("{:.2}", self.ms_clip.get()),
+            12 => self.format_level(self.mid_threshold.get()),
+            13 => self.format_level(self.side_threshold.get()),
+            14 => format!("{:.0} bits", crush_to_bits(self.crush.get())),
+            15 => format!("{:.2}", self.gate.get()),
+            16 => GATE_RATE_NAMES[self.gate_rate.get() as usize].to_string(),
+            GATE_STEP_FIRST..=GATE_STEP_LAST => format!(
+                "{:.2}",
+                self.gate_steps[(index - GATE_STEP_FIRST) as usize].get()
+            ),
+            33 => INPUT_SOURCE_NAMES[self.input_source.get() as usize].to_string(),
+            34 => format!("{:.0}%", self.tape.get() * 100.0),
+            35 => ROUTING_MODE_NAMES[self.routing_mode.get() as usize].to_string(),
+            36 => format!("{:.1} dB", self.emphasis.get() * EMPHASIS_DB_MAX),
+            37 => format!("{:.2}", self.freeze.get()),
+            38 => format!("{:.2}", self.clip2.get()),
+            39 => CLIP_MODE_NAMES[self.clip2_mode.get() as usize].to_string(),
+            40 => self.format_level(self.clip2_threshold.get()),
+            41 => format!("{:.2}", self.dry_bleed.get()),
+            42 => format!("{:.2}", self.glue.get()),
+            43 => self.format_level(self.glue_threshold.get()),
+            44 => format!("{:.1}{}", self.glue_ratio.get(), unit),
+            45 => format!("{:.0} {}", self.glue_attack.get(), unit),
+            46 => format!("{:.0} {}", self.glue_release.get(), unit),
+            47 => format!("{:.1} {}", self.smoothing_ms.get(), unit),
+            48 => CURVE_NAMES[self.curve.get() as usize].to_string(),
+            49 => {
+                if self.quant_levels.get() < 2.0 {
+                    "Follow crush".to_string()
+                } else {
+                    format!("{:.0} {}", self.quant_levels.get(), unit)
+                }
+            }
+            50 => format!("{:.2}", self.mu_law.get()),
+            51 => format!("{:.0}", self.seed.get()),
+            52 => format!("{:.2}", self.compact.get()),
+            53 => LEVEL_UNIT_NAMES[self.level_unit.get() as usize].to_string(),
+            54 => SILENCE_POSITION_NAMES[self.silence_position.get() as usize].to_string(),
+            55 => format!("{:.1} {}", self.threshold_smoothing_ms.get(), unit),
+            56 => format!("{:.2}", self.safety_lpf.get()),
+            57 => format!("{:.2}", self.legacy_silence.get()),
+            58 => format!("{:.0}%", self.transient.get() * 100.0),
+            59 => GAIN_RANGE_NAMES[self.gain_range.get() as usize].to_string(),
+            60 => format!("{:.2}", self.side_image.get()),
+            61 => format!("{:.0}%", self.wow.get() * 100.0),
+            62 => format!("{:.0}%", self.flutter.get() * 100.0),
+            63 => format!("{:.2}", self.silence_link.get()),
+            64 => {
+                if self.sidechain_hpf.get() <= SIDECHAIN_HPF_MIN {
+                    "Off".to_string()
+                } else {
+                    format!("{:.0} {}", self.sidechain_hpf.get(), unit)
+                }
+            }
+            65 => format!("{:.2}", self.true_peak_meter.get()),
+            66 => format!("{:.0}%", self.amount.get() * 100.0),
+            67 => format!("{:.2}", self.parallel.get()),
+            68 => format!("{:.0} {}", self.wet_hpf.get(), unit),
+            69 => TEST_SIGNAL_NAMES[self.test_signal.get() as usize].to_string(),
+            70 => format!("{:.0} {}", self.test_freq.get(), unit),
+            71 => format!("{:.0}%", self.analog_drift.get() * 100.0),
+            72 => STAGE_ORDER_NAMES[self.stage_order.get() as usize].to_string(),
+            73 => GATE_CURVE_NAMES[self.gate_curve.get() as usize].to_string(),
+            74 => format!("{:.2}", self.dry_match.get()),
+            75 => format!("{:.2}", self.lite_ui.get()),
+            76 => GAIN_MODE_NAMES[self.gain_mode.get() as usize].to_string(),
+            77 => format!("{:.0}%", self.sat_floor.get() * 100.0),
+            78 => METER_BALLISTICS_NAMES[self.meter_ballistics.get() as usize].to_string(),
+            79 => format!("{:.1} {}", self.input_trim.get(), unit),
+            80 => CRUSH_STEREO_NAMES[self.crush_stereo.get() as usize].to_string(),
+            81 => format!("{:.0} {}", self.glue_hold.get(), unit),
+            82 => freeze_note_text(self.freeze_note.get()),
+            83 => FREEZE_CHANNEL_NAMES[self.freeze_channel.get() as usize].to_string(),
+            84 => format!("{:.2}", self.power_save.get()),
+            85 => format!("{:.2}", self.side_limit.get()),
+            86 => format!("{:.2}", self.compare.get()),
+            87 => format!("{:.2}", self.level_match.get()),
             _ => "".to_string(),
         }
     }
@@ -76,8 +1458,293 @@ impl PluginParameters for EffectParams {
             1 => "8-bitify",
             2 => "Mix",
             3 => "Gain",
+            4 => "Invert L",
+            5 => "Invert R",
+            6 => "Ceiling",
+            7 => "Bass Mono",
+            8 => "Mono Freq",
+            9 => "Clip Mode",
+            10 => "Dither",
+            11 => "M/S Clip",
+            12 => "Mid Thresh",
+            13 => "Side Thresh",
+            14 => "Crush",
+            15 => "Gate",
+            16 => "Gate Rate",
+            GATE_STEP_FIRST..=GATE_STEP_LAST => GATE_STEP_NAMES[(index - GATE_STEP_FIRST) as usize],
+            33 => "Input Source",
+            34 => "Tape",
+            35 => "Routing",
+            36 => "Emphasis",
+            37 => "Freeze",
+            38 => "Clip 2",
+            39 => "Clip 2 Mode",
+            40 => "Clip 2 Thresh",
+            41 => "Dry Bleed",
+            42 => "Glue",
+            43 => "Glue Thresh",
+            44 => "Glue Ratio",
+            45 => "Glue Attack",
+            46 => "Glue Release",
+            47 => "Smoothing",
+            48 => "Curve",
+            49 => "Quant Levels",
+            50 => "Mu-law",
+            51 => "Seed",
+            52 => "Compact",
+            53 => "Level Unit",
+            54 => "Silence Gate",
+            55 => "Thresh Smooth",
+            56 => "Safety LPF",
+            57 => "Legacy Gate",
+            58 => "Transient",
+            59 => "Gain Range",
+            60 => "Side Image",
+            61 => "Wow",
+            62 => "Flutter",
+            63 => "Silence Link",
+            64 => "Glue SC HPF",
+            65 => "True Peak Mtr",
+            66 => "Amount",
+            67 => "Parallel",
+            68 => "Wet HPF",
+            69 => "Test Signal",
+            70 => "Test Freq",
+            71 => "Analog Drift",
+            72 => "Stage Order",
+            73 => "Gate Curve",
+            74 => "Dry Match",
+            75 => "Lite UI",
+            76 => "Gain Mode",
+            77 => "Sat Floor",
+            78 => "Meter Ballistics",
+            79 => "Input Trim",
+            80 => "Crush Stereo",
+            81 => "Glue Hold",
+            82 => "Freeze Note",
+            83 => "Freeze Channel",
+            84 => "Power Save",
+            85 => "Side Limit",
+            86 => "Compare",
+            87 => "Level Match",
             _ => "",
         }
         .to_string()
     }
+
+    // reseeding mid-song would change every render after it, the seed only comes
+    // with the project
+    fn can_be_automated(&self, index: i32) -> bool {
+        index != 51
+    }
+
+    fn get_preset_data(&self) -> Vec<u8> {
+        self.to_chunk()
+    }
+
+    fn get_bank_data(&self) -> Vec<u8> {
+        self.to_chunk()
+    }
+
+    fn load_preset_data(&self, data: &[u8]) {
+        self.load_chunk(data);
+    }
+
+    fn load_bank_data(&self, data: &[u8]) {
+        self.load_chunk(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gate_rate_out_of_range_stays_on_the_table() {
+        assert_eq!(gate_rate_beats(2.0), 0.25);
+        for index in [4.0, 1e9, f32::INFINITY] {
+            assert_eq!(gate_rate_beats(index), 0.125);
+        }
+        // NaN and negatives cast to zero
+        assert_eq!(gate_rate_beats(f32::NAN), 1.0);
+        assert_eq!(gate_rate_beats(-3.0), 1.0);
+    }
+
+    // a saved chunk with every value swapped for `val`
+    fn chunk_with(val: f32) -> Vec<u8> {
+        let mut data = EffectParams::default().to_chunk();
+        for index in 0..PARAM_NUM as usize {
+            let offset = 12 + index * 4;
+            data[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn defaults_match_the_metadata() {
+        let params = EffectParams::default();
+        for index in 0..PARAM_NUM {
+            let info = param_info(index);
+            assert_eq!(
+                params.param_at(index).unwrap().get(),
+                info.default,
+                "param {}",
+                index
+            );
+            assert!(
+                info.min <= info.default && info.default <= info.max,
+                "param {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn gain_round_trips_in_every_range() {
+        let params = EffectParams::default();
+        for range in 0..GAIN_RANGE_NAMES.len() {
+            params.set_parameter(
+                59,
+                choice_to_normalized(range as f32, GAIN_RANGE_NAMES.len()),
+            );
+            for step in 0..=1000 {
+                let val = step as f32 / 1000.0;
+                params.set_parameter(3, val);
+                let back = params.get_parameter(3);
+                assert!(
+                    (back - val).abs() < 1e-4,
+                    "range {}: {} came back {}",
+                    range,
+                    val,
+                    back
+                );
+            }
+        }
+        // a gain that somehow ended up outside the range still reads as a valid host value
+        params.gain.set(0.001);
+        assert_eq!(params.get_parameter(3), 0.0);
+    }
+
+    #[test]
+    fn level_units_agree() {
+        for (db, percent) in [
+            (0.0, "100.0%"),
+            (-6.0, "50.1%"),
+            (-12.0, "25.1%"),
+            (-20.0, "10.0%"),
+        ] {
+            let linear = to_linear(db);
+            assert_eq!(LevelUnit::Db.format(linear), format!("{:.2} dB", db));
+            assert_eq!(LevelUnit::Percent.format(linear), percent);
+        }
+        // the threshold's host text follows the unit, it defaults to -12 dB
+        let params = EffectParams::default();
+        assert_eq!(params.get_parameter_text(0), "-12.00 dB");
+        params.level_unit.set(1.0);
+        assert_eq!(params.get_parameter_text(0), "25.1%");
+    }
+
+    #[test]
+    fn bad_values_load_as_defaults() {
+        for val in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            let params = EffectParams::default();
+            params.load_chunk(&chunk_with(val));
+            for index in 0..PARAM_NUM {
+                let field = params.param_at(index).unwrap();
+                assert_eq!(field.get(), param_info(index).default, "param {}", index);
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_values_load_clamped() {
+        let (low, high) = (EffectParams::default(), EffectParams::default());
+        low.load_chunk(&chunk_with(-5.0));
+        high.load_chunk(&chunk_with(5.0));
+        // freeze always loads released
+        for index in (0..PARAM_NUM).filter(|&index| index != 37) {
+            assert_eq!(low.get_parameter(index), 0.0, "param {}", index);
+            assert_eq!(high.get_parameter(index), 1.0, "param {}", index);
+        }
+    }
+
+    #[test]
+    fn label_round_trips_and_bad_ones_load_empty() {
+        let params = EffectParams::default();
+        let loaded = EffectParams::default();
+        params.set_label("drums crush ✓");
+        loaded.load_chunk(&params.to_chunk());
+        assert_eq!(loaded.label(), "drums crush ✓");
+        // too long is cut short without splitting a char
+        params.set_label(&"é".repeat(40));
+        assert_eq!(params.label(), "é".repeat(LABEL_MAX_BYTES / 2));
+        // the length sits right after the values
+        let offset = 12 + PARAM_NUM as usize * 4;
+        let mut data = params.to_chunk();
+        data[offset..offset + 4].copy_from_slice(&1000_u32.to_le_bytes());
+        loaded.load_chunk(&data);
+        assert_eq!(loaded.label(), "");
+        let mut data = params.to_chunk();
+        data[offset + 4] = 0xff;
+        loaded.set_label("vocal warm");
+        loaded.load_chunk(&data);
+        assert_eq!(loaded.label(), "");
+    }
+
+    #[test]
+    fn curve_points_round_trip() {
+        let params = EffectParams::default();
+        let points = [(0.0, 0.0), (0.2, 0.5), (0.45, 0.6), (0.7, 0.95), (1.0, 1.0)];
+        params.set_curve_points(&points);
+        let loaded = EffectParams::default();
+        loaded.load_chunk(&params.to_chunk());
+        assert_eq!(loaded.curve_points(), points);
+        assert!(loaded.curve_changed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn dragging_between_the_closest_neighbours_never_panics() {
+        for step in 1..1000 {
+            let start = step as f32 / 1000.0 * 0.9;
+            // each point as close to the last as the sanitizer lets it be
+            let packed: Vec<(f32, f32)> = (0..3)
+                .map(|index| (start + index as f32 * CURVE_POINT_GAP, 0.2 * index as f32))
+                .collect();
+            let points = sanitize_curve_points(&packed);
+            for index in 1..points.len() {
+                for target in [(0.0, 0.0), (0.5, 0.5), (1.0, 1.0)] {
+                    let (x, y) = drag_curve_point(&points, index, target);
+                    assert!(x >= points[index - 1].0 && y >= points[index - 1].1);
+                    if index < points.len() - 1 {
+                        assert!(x <= points[index + 1].0 && y <= points[index + 1].1);
+                    } else {
+                        assert_eq!(x, 1.0);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn freeze_is_never_saved_or_loaded() {
+        let params = EffectParams::default();
+        params.set_parameter(37, 1.0);
+        let data = params.to_chunk();
+        assert_eq!(&data[12 + 37 * 4..12 + 38 * 4], &0.0_f32.to_le_bytes());
+        params.load_chunk(&chunk_with(1.0));
+        assert_eq!(params.freeze.get(), 0.0);
+    }
+
+    #[test]
+    fn seed_stays_out_of_automation_but_not_the_chunk() {
+        let params = EffectParams::default();
+        assert!(!params.can_be_automated(51));
+        assert!((0..PARAM_NUM)
+            .filter(|&index| index != 51)
+            .all(|index| params.can_be_automated(index)));
+        params.seed.set(1234.0);
+        let restored = EffectParams::default();
+        restored.load_chunk(&params.to_chunk());
+        assert_eq!(restored.seed.get(), 1234.0);
+    }
 }
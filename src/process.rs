@@ -1,26 +1,1375 @@
-use crate::param::EffectParams;
+use std::f32::consts::{FRAC_1_SQRT_2, TAU};
+use std::sync::atomic::Ordering;
+
+use crate::analysis::{block_peak, EnvelopeFollower, TruePeak};
+use crate::filter::{Biquad, Crossover};
+use crate::generator::TestSignal;
+use crate::param::{
+    crush_to_bits, gate_rate_beats, ClipMode, CrushStereo, Curve, EffectParams, GainMode,
+    GateCurve, RoutingMode, SilencePosition, StageOrder, TestSignalMode, EMPHASIS_DB_MAX,
+    GATE_STEPS, SIDECHAIN_HPF_MIN,
+};
+use crate::shaper::Waveshaper;
+use crate::smoothing::SmoothedParam;
+use crate::util::{one_pole_coef, sanitize, to_db, to_linear, Rng};
 
 // The threshold below which to drop the signal
 const SILENT_THRESHOLD_DB: f32 = 0.015_848_933;
 const SILENT_THRESHOLD_COUNT: i32 = 32;
 
-/**
+// Where the split clip mode divides lows (hard clip) from highs (soft clip)
+const SPLIT_CLIP_FREQ: f32 = 2000.0;
+
+// How far above the threshold the shave mode lets peaks round off, relative to the threshold
+const SHAVE_KNEE: f32 = 0.25;
+
+// Saturation floor, how far below the threshold the knee starts at full amount, relative to
+// the threshold
+const SAT_FLOOR_KNEE: f32 = 0.5;
+
+// Output dither targets this bit depth
+const DITHER_BITS: i32 = 16;
+const DITHER_SEED: u32 = 0x2b9e_3779;
+
+// Analog drift, at full amount each side's clip threshold sits up to this far off,
+// fixed per instance like a pair of mismatched components
+const DRIFT_MAX_DB: f32 = 1.0;
+const DRIFT_SEED: u32 = 0x5eed_d71f;
+
+// How long switching a mode or toggle crossfades between old and new
+const MODE_FADE_MS: f32 = 5.0;
+
+// mu-law companding constant, same as telephone codecs
+const MU_LAW_MU: f32 = 255.0;
+
+// At this many bits and under, truncating leaves no level between silence and full scale
+const MID_RISE_BITS: f32 = 2.0;
+
+// How long the crush resolution takes to glide to a new setting
+const CRUSH_SMOOTH_MS: f32 = 5.0;
+
+// How quickly the trance gate opens and closes
+const GATE_SMOOTH_MS: f32 = 2.0;
+
+// Tape amount scales all of these from nothing up to the given maximum
+// saturation drive into the tanh curve
+const TAPE_DRIVE_MAX: f32 = 4.0;
+// the top end rolls off from CUTOFF_MAX down to CUTOFF_MIN
+const TAPE_CUTOFF_MAX: f32 = 20000.0;
+const TAPE_CUTOFF_MIN: f32 = 6000.0;
+// slow compression above the threshold, ratio goes from 1:1 up to 1 + RATIO_MAX : 1
+const TAPE_COMP_THRESHOLD_DB: f32 = -12.0;
+const TAPE_RATIO_MAX: f32 = 3.0;
+const TAPE_ATTACK_MS: f32 = 30.0;
+const TAPE_RELEASE_MS: f32 = 300.0;
+
+// Corner of the pre/de-emphasis shelves around the clipper
+const EMPHASIS_FREQ: f32 = 3000.0;
+
+// Transient emphasis, a fast and a slow follower share the release, the attack
+// portion is where the fast one runs ahead
+const TRANSIENT_FAST_MS: f32 = 1.0;
+const TRANSIENT_SLOW_MS: f32 = 30.0;
+const TRANSIENT_RELEASE_MS: f32 = 150.0;
+const TRANSIENT_GAIN_MAX_DB: f32 = 12.0;
+
+// Wow and flutter, a slow and a fast wobble of a short delay line, depths at full
+// setting. The delay swings between zero and twice the depth so it passes straight
+// through when both are off
+const WOW_RATE_HZ: f32 = 0.5;
+const WOW_DEPTH_MS: f32 = 2.0;
+const FLUTTER_RATE_HZ: f32 = 7.0;
+const FLUTTER_DEPTH_MS: f32 = 0.15;
+const WOW_SMOOTH_MS: f32 = 50.0;
+
+// Freeze loops this much of the most recent output, crossfading the loop point
+const FREEZE_MS: f32 = 250.0;
+const FREEZE_LOOP_FADE_MS: f32 = 5.0;
+
+// How much dry signal the bleed toggle always lets through, even fully wet
+const DRY_BLEED: f32 = 0.03;
+
+// Level matched compare, wet and dry loudness are mean squares over about this long, the
+// makeup on the dry signal stops here so one side falling silent can't blow the other up
+const LEVEL_MATCH_MS: f32 = 1000.0;
+const LEVEL_MATCH_DB_MAX: f32 = 24.0;
+
+// Output envelope for the editor's level bar
+const ENVELOPE_ATTACK_MS: f32 = 10.0;
+const ENVELOPE_RELEASE_MS: f32 = 200.0;
+
+// Safety low-pass, fades in over the wet signal once the clipper is driven this hard
+const SAFETY_LPF_FREQ: f32 = 18000.0;
+const SAFETY_DRIVE_DB: f32 = 12.0;
+
+// Power save, the input has to stay below this level for this long before processing is skipped
+const POWER_SAVE_THRESHOLD_DB: f32 = -120.0;
+const POWER_SAVE_MS: f32 = 500.0;
+
+// Host song position for the tempo synced stages
+#[derive(Default)]
+pub struct Transport {
+    pub ppq_pos: f64,
+    pub tempo: f64,
+    pub is_playing: bool,
+}
+
+impl Transport {
+    // song position in beats for hosts that only report it in samples
+    pub fn ppq_from_samples(sample_pos: f64, tempo: f64, sample_rate: f64) -> f64 {
+        if sample_rate > 0.0 {
+            sample_pos / sample_rate * tempo / 60.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/*
+ * DSP state that has to survive between blocks
+ */
+
+pub struct DspState {
+    pub sample_rate: f32,
+    mono_maker: [Crossover; 2],
+    mono_maker_freq: f32,
+    split_clip: [Crossover; 2],
+    // tables are built once here, never on the audio thread
+    shaper: Waveshaper,
+    // second clip stage, runs in place so it clips a copy of the first stage's output
+    clip2_mode: ClipMode,
+    clip2_split: [Crossover; 2],
+    clip2_buf: [Vec<f32>; 2],
+    // how far off each side's threshold is, -1 to 1, from the seed
+    drift: [f32; 2],
+    drift_threshold_buf: [Vec<f32>; 2],
+    // last two quantization errors per channel, for noise shaping
+    dither_error: [[f32; 2]; 2],
+    dither_rng: Rng,
+    // per instance seed every random stage starts from
+    seed: u32,
+    // clip mode crossfade, the previous mode keeps running until the fade is done
+    clip_mode: ClipMode,
+    clip_mode_prev: ClipMode,
+    clip_fade: f32,
+    clip_fade_buf: [Vec<f32>; 2],
+    // how much of the 8-bitified signal is let through
+    bitify_amount: f32,
+    // quantization steps per unit of level, glides towards the crush setting
+    crush_resolution: f32,
+    gate_gain: f32,
+    // song position the gate pattern counts its steps from
+    gate_anchor: f64,
+    tape_filter: [Biquad; 2],
+    tape_cutoff: f32,
+    tape_env: f32,
+    // pre-emphasis before the clipper, de-emphasis after it
+    emphasis_pre: [Biquad; 2],
+    emphasis_post: [Biquad; 2],
+    emphasis_db: f32,
+    // wow and flutter delay line, LFO phases in cycles and the glided depths in ms
+    wow_buf: [Vec<f32>; 2],
+    wow_write: usize,
+    wow_phase: f32,
+    flutter_phase: f32,
+    wow_depth: f32,
+    flutter_depth: f32,
+    // detection-only high-pass for the glue and the corner it's set to
+    sidechain_filter: [Biquad; 2],
+    sidechain_freq: f32,
+    // parallel mode's wet high-pass and its corner, zero while the mode is off
+    wet_filter: [Biquad; 2],
+    wet_freq: f32,
+    // the tape's roll-off copied onto the dry signal and its corner, zero while unused
+    dry_match_filter: [Biquad; 2],
+    dry_match_cutoff: f32,
+    // slow mean squares of the wet and the dry signal for the level match
+    level_match_power: [f32; 2],
+    test_signal: TestSignal,
+    // fast and slow envelopes of the transient emphasis
+    transient_env: [f32; 2],
+    // what the clipper reads when the glue or pre-emphasis has to change it, the dry input stays as is
+    clip_in_buf: [Vec<f32>; 2],
+    // glue compressor gain reduction in dB
+    glue_reduction: f32,
+    // samples left before the glue may start to release
+    glue_hold_left: u32,
+    // glides for the params that zipper when they jump
+    threshold_smooth: SmoothedParam,
+    threshold_buf: Vec<f32>,
+    clip2_threshold_smooth: SmoothedParam,
+    clip2_threshold_buf: Vec<f32>,
+    gain_smooth: SmoothedParam,
+    gain_buf: Vec<f32>,
+    trim_smooth: SmoothedParam,
+    mix_smooth: SmoothedParam,
+    // ring of the latest output, preallocated in set_sample_rate
+    freeze_buf: [Vec<f32>; 2],
+    freeze_write: usize,
+    // where the loop begins in the ring and how far into the loop playback is
+    freeze_start: usize,
+    freeze_read: usize,
+    is_frozen: bool,
+    freeze_amount: f32,
+    // last resort harshness filter and how far it is faded in
+    safety_filter: [Biquad; 2],
+    safety_amount: f32,
+    envelope: EnvelopeFollower,
+    // oversampled peak detection for the output meter
+    true_peak: [TruePeak; 2],
+    // consecutive quiet samples per channel seen by remove_silence, carried across blocks
+    silence_counter: [i32; 2],
+    // power save, left alone by reset() so a reset doesn't wake it
+    silent_samples: usize,
+    is_sleeping: bool,
+    wake_fade: f32,
+}
+
+impl Default for DspState {
+    fn default() -> DspState {
+        let mut state = DspState {
+            sample_rate: 44100.0,
+            mono_maker: [Crossover::default(); 2],
+            // force the crossover to be set up on the first block
+            mono_maker_freq: 0.0,
+            split_clip: [Crossover::default(); 2],
+            shaper: Waveshaper::default(),
+            clip2_mode: ClipMode::Hard,
+            clip2_split: [Crossover::default(); 2],
+            clip2_buf: [Vec::new(), Vec::new()],
+            drift: drift_offsets(0),
+            drift_threshold_buf: [Vec::new(), Vec::new()],
+            dither_error: [[0.0; 2]; 2],
+            dither_rng: Rng::new(DITHER_SEED),
+            seed: 0,
+            clip_mode: ClipMode::Hard,
+            clip_mode_prev: ClipMode::Hard,
+            clip_fade: 1.0,
+            clip_fade_buf: [Vec::new(), Vec::new()],
+            bitify_amount: 0.0,
+            crush_resolution: 0.0,
+            gate_gain: 1.0,
+            gate_anchor: 0.0,
+            safety_filter: [Biquad::default(); 2],
+            safety_amount: 0.0,
+            envelope: EnvelopeFollower::default(),
+            true_peak: [TruePeak::default(); 2],
+            silence_counter: [0; 2],
+            tape_filter: [Biquad::default(); 2],
+            // force the roll-off to be set up on the first block
+            tape_cutoff: 0.0,
+            tape_env: 0.0,
+            emphasis_pre: [Biquad::default(); 2],
+            emphasis_post: [Biquad::default(); 2],
+            emphasis_db: 0.0,
+            sidechain_filter: [Biquad::default(); 2],
+            sidechain_freq: 0.0,
+            wet_filter: [Biquad::default(); 2],
+            wet_freq: 0.0,
+            dry_match_filter: [Biquad::default(); 2],
+            dry_match_cutoff: 0.0,
+            level_match_power: [0.0; 2],
+            test_signal: TestSignal::default(),
+            transient_env: [0.0; 2],
+            wow_buf: [Vec::new(), Vec::new()],
+            wow_write: 0,
+            wow_phase: 0.0,
+            flutter_phase: 0.0,
+            wow_depth: 0.0,
+            flutter_depth: 0.0,
+            clip_in_buf: [Vec::new(), Vec::new()],
+            glue_reduction: 0.0,
+            glue_hold_left: 0,
+            threshold_smooth: SmoothedParam::default(),
+            threshold_buf: Vec::new(),
+            clip2_threshold_smooth: SmoothedParam::default(),
+            clip2_threshold_buf: Vec::new(),
+            gain_smooth: SmoothedParam::default(),
+            gain_buf: Vec::new(),
+            trim_smooth: SmoothedParam::default(),
+            mix_smooth: SmoothedParam::default(),
+            freeze_buf: [Vec::new(), Vec::new()],
+            freeze_write: 0,
+            freeze_start: 0,
+            freeze_read: 0,
+            is_frozen: false,
+            freeze_amount: 0.0,
+            silent_samples: 0,
+            is_sleeping: false,
+            wake_fade: 1.0,
+        };
+        state.set_sample_rate(44100.0);
+        state
+    }
+}
+
+impl DspState {
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        for crossover in self
+            .split_clip
+            .iter_mut()
+            .chain(self.clip2_split.iter_mut())
+        {
+            crossover.set_frequency(SPLIT_CLIP_FREQ, sample_rate);
+        }
+        for filter in &mut self.safety_filter {
+            filter.set_low_pass(SAFETY_LPF_FREQ, FRAC_1_SQRT_2, sample_rate);
+        }
+        self.envelope
+            .set_times(ENVELOPE_ATTACK_MS, ENVELOPE_RELEASE_MS, sample_rate);
+        // the rings are allocated here and the block buffers in set_block_size, off the audio thread
+        let freeze_len = (FREEZE_MS / 1000.0 * sample_rate) as usize;
+        for buf in &mut self.freeze_buf {
+            buf.clear();
+            buf.resize(freeze_len, 0.0);
+        }
+        // room for the widest swing plus the interpolation neighbour
+        let wow_len = (2.0 * (WOW_DEPTH_MS + FLUTTER_DEPTH_MS) / 1000.0 * sample_rate) as usize + 2;
+        for buf in &mut self.wow_buf {
+            buf.clear();
+            buf.resize(wow_len, 0.0);
+        }
+        self.reset();
+    }
+
+    // sizes the per block scratch buffers, kept off the audio thread like the rings above
+    pub fn set_block_size(&mut self, block_size: usize) {
+        for buf in self
+            .clip_in_buf
+            .iter_mut()
+            .chain(self.drift_threshold_buf.iter_mut())
+            .chain(self.clip_fade_buf.iter_mut())
+            .chain(self.clip2_buf.iter_mut())
+            .chain([
+                &mut self.threshold_buf,
+                &mut self.clip2_threshold_buf,
+                &mut self.gain_buf,
+            ])
+        {
+            buf.resize(block_size, 0.0);
+        }
+    }
+
+    // clear filter memories and recompute coefficients on the next block
+    pub fn reset(&mut self) {
+        for crossover in self
+            .mono_maker
+            .iter_mut()
+            .chain(self.split_clip.iter_mut())
+            .chain(self.clip2_split.iter_mut())
+        {
+            crossover.reset();
+        }
+        self.mono_maker_freq = 0.0;
+        self.dither_error = [[0.0; 2]; 2];
+        // same seed, same noise from the start of every render
+        self.dither_rng = Rng::new(self.seed ^ DITHER_SEED);
+        self.clip_fade = 1.0;
+        self.bitify_amount = 0.0;
+        self.crush_resolution = 0.0;
+        self.gate_gain = 1.0;
+        self.gate_anchor = 0.0;
+        for filter in &mut self.tape_filter {
+            filter.reset();
+        }
+        self.tape_cutoff = 0.0;
+        self.tape_env = 0.0;
+        for filter in self
+            .emphasis_pre
+            .iter_mut()
+            .chain(self.emphasis_post.iter_mut())
+        {
+            filter.reset();
+        }
+        self.emphasis_db = 0.0;
+        self.transient_env = [0.0; 2];
+        for filter in &mut self.sidechain_filter {
+            filter.reset();
+        }
+        self.sidechain_freq = 0.0;
+        for filter in &mut self.wet_filter {
+            filter.reset();
+        }
+        self.wet_freq = 0.0;
+        for filter in &mut self.dry_match_filter {
+            filter.reset();
+        }
+        self.dry_match_cutoff = 0.0;
+        self.level_match_power = [0.0; 2];
+        self.test_signal.reset();
+        for buf in &mut self.wow_buf {
+            buf.fill(0.0);
+        }
+        self.wow_write = 0;
+        self.wow_phase = 0.0;
+        self.flutter_phase = 0.0;
+        self.wow_depth = 0.0;
+        self.flutter_depth = 0.0;
+        self.glue_reduction = 0.0;
+        self.glue_hold_left = 0;
+        for smoother in self.smoothers() {
+            smoother.reset();
+        }
+        for buf in &mut self.freeze_buf {
+            buf.fill(0.0);
+        }
+        self.freeze_write = 0;
+        self.freeze_read = 0;
+        self.is_frozen = false;
+        self.freeze_amount = 0.0;
+        self.silence_counter = [0; 2];
+        for filter in &mut self.safety_filter {
+            filter.reset();
+        }
+        self.safety_amount = 0.0;
+        self.envelope.reset();
+        for true_peak in &mut self.true_peak {
+            true_peak.reset();
+        }
+    }
+
+    // reset() and then some: wakes from power save and fades back in from silence, for
+    // when something got stuck and the output has to come back clean right away
+    pub fn panic(&mut self) {
+        self.reset();
+        self.silent_samples = 0;
+        self.is_sleeping = false;
+        self.wake_fade = 0.0;
+    }
+
+    fn set_seed(&mut self, seed: u32) {
+        if seed != self.seed {
+            self.seed = seed;
+            self.dither_rng = Rng::new(seed ^ DITHER_SEED);
+            self.drift = drift_offsets(seed);
+            self.test_signal.set_seed(seed);
+        }
+    }
+
+    // every per sample glide, for the bulk operations
+    fn smoothers(&mut self) -> [&mut SmoothedParam; 5] {
+        [
+            &mut self.threshold_smooth,
+            &mut self.clip2_threshold_smooth,
+            &mut self.gain_smooth,
+            &mut self.mix_smooth,
+            &mut self.trim_smooth,
+        ]
+    }
+
+    // the clamp threshold has its own time, the rest share one
+    fn set_smoothing_time(&mut self, time_ms: f32, threshold_time_ms: f32) {
+        let sample_rate = self.sample_rate;
+        for smoother in self.smoothers() {
+            smoother.set_time(time_ms, sample_rate);
+        }
+        self.threshold_smooth
+            .set_time(threshold_time_ms, sample_rate);
+    }
+
+    // flush denormals out of the recursive state, start over if anything blew up,
+    // true when it had to
+    pub fn sanitize(&mut self) -> bool {
+        let mut is_sane = true;
+        for crossover in self
+            .mono_maker
+            .iter_mut()
+            .chain(self.split_clip.iter_mut())
+            .chain(self.clip2_split.iter_mut())
+        {
+            is_sane &= crossover.sanitize();
+        }
+        for error in self.dither_error.iter_mut().flatten() {
+            is_sane &= sanitize(error);
+        }
+        for filter in self
+            .tape_filter
+            .iter_mut()
+            .chain(self.emphasis_pre.iter_mut())
+            .chain(self.emphasis_post.iter_mut())
+            .chain(self.sidechain_filter.iter_mut())
+            .chain(self.wet_filter.iter_mut())
+            .chain(self.dry_match_filter.iter_mut())
+        {
+            is_sane &= filter.sanitize();
+        }
+        for env in self
+            .transient_env
+            .iter_mut()
+            .chain(self.level_match_power.iter_mut())
+        {
+            is_sane &= sanitize(env);
+        }
+        is_sane &= self.envelope.sanitize();
+        is_sane &= sanitize(&mut self.clip_fade)
+            & sanitize(&mut self.bitify_amount)
+            & sanitize(&mut self.crush_resolution)
+            & sanitize(&mut self.gate_gain)
+            & sanitize(&mut self.tape_env)
+            & sanitize(&mut self.glue_reduction);
+        for smoother in self.smoothers() {
+            is_sane &= smoother.sanitize();
+        }
+
+        if !is_sane {
+            self.reset();
+        }
+        !is_sane
+    }
+}
+
+/*
  * manipulating samples functions
  */
 
-fn mix((in_l, in_r): (&[f32], &[f32]), (out_l, out_r): (&mut [f32], &mut [f32]), mix: f32) {
-    // Mix it L
-    for (out_buf_l_sample, in_buf_l_sample) in out_l.iter_mut().zip(in_l.iter()) {
-        *out_buf_l_sample = (*out_buf_l_sample * mix) + ((1.0 - mix) * in_buf_l_sample);
+// tanh curve that bends towards the threshold instead of hitting it
+fn soft_clip(sample: f32, threshold: f32) -> f32 {
+    // the host can pull the threshold all the way down, 0/0 would be NaN on silence
+    if threshold <= 0.0 {
+        return 0.0;
+    }
+    threshold * (sample / threshold).tanh()
+}
+
+// leaves everything below the threshold alone, only the part above it gets soft clipped
+fn shave(sample: f32, threshold: f32) -> f32 {
+    let excess = sample.abs() - threshold;
+    if excess <= 0.0 {
+        return sample;
+    }
+    let knee = threshold * SHAVE_KNEE;
+    (threshold + soft_clip(excess, knee)).copysign(sample)
+}
+
+// cubic knee over the top of the range, the amount sets how far down it reaches,
+// untouched below it and flattening out right on the threshold. Shave and split let
+// peaks past the threshold, those pass as they are
+fn sat_floor(sample: f32, threshold: f32, amount: f32) -> f32 {
+    let width = threshold * SAT_FLOOR_KNEE * amount;
+    let knee = threshold - width;
+    let level = sample.abs();
+    if width <= 0.0 || level <= knee || level >= threshold {
+        return sample;
+    }
+    // unity slope where it leaves the straight line, zero slope at the threshold
+    let position = (level - knee) / width;
+    let bent = position + position * position - position * position * position;
+    (knee + width * bent).copysign(sample)
+}
+
+// fixed per seed, so a saved project always drifts the same way
+fn drift_offsets(seed: u32) -> [f32; 2] {
+    let mut rng = Rng::new(seed ^ DRIFT_SEED);
+    [rng.next_f32() * 2.0 - 1.0, rng.next_f32() * 2.0 - 1.0]
+}
+
+fn clip(
+    mode: ClipMode,
+    (in_l, in_r): (&[f32], &[f32]),
+    (out_l, out_r): (&mut [f32], &mut [f32]),
+    (split_clip, split_gains): (&mut [Crossover; 2], (f32, f32)),
+    shaper: &Waveshaper,
+    (thresholds_l, thresholds_r): (&[f32], &[f32]),
+) {
+    match mode {
+        ClipMode::Hard => {
+            // Clamp L
+            for ((out_buf_l_sample, in_buf_l_sample), threshold) in
+                out_l.iter_mut().zip(in_l.iter()).zip(thresholds_l)
+            {
+                *out_buf_l_sample = in_buf_l_sample.clamp(-threshold, *threshold);
+            }
+
+            // Clamp R
+            for ((out_buf_r_sample, in_buf_r_sample), threshold) in
+                out_r.iter_mut().zip(in_r.iter()).zip(thresholds_r)
+            {
+                *out_buf_r_sample = in_buf_r_sample.clamp(-threshold, *threshold);
+            }
+        }
+        ClipMode::Split => clip_split(
+            (in_l, in_r),
+            (out_l, out_r),
+            (split_clip, split_gains),
+            (thresholds_l, thresholds_r),
+        ),
+        ClipMode::Shave => {
+            // Shave the peaks L
+            for ((out_buf_l_sample, in_buf_l_sample), threshold) in
+                out_l.iter_mut().zip(in_l.iter()).zip(thresholds_l)
+            {
+                *out_buf_l_sample = shave(*in_buf_l_sample, *threshold);
+            }
+
+            // Shave the peaks R
+            for ((out_buf_r_sample, in_buf_r_sample), threshold) in
+                out_r.iter_mut().zip(in_r.iter()).zip(thresholds_r)
+            {
+                *out_buf_r_sample = shave(*in_buf_r_sample, *threshold);
+            }
+        }
+        ClipMode::Curve => {
+            // Shape L
+            for ((out_buf_l_sample, in_buf_l_sample), threshold) in
+                out_l.iter_mut().zip(in_l.iter()).zip(thresholds_l)
+            {
+                *out_buf_l_sample = shaper.process(*in_buf_l_sample, *threshold);
+            }
+
+            // Shape R
+            for ((out_buf_r_sample, in_buf_r_sample), threshold) in
+                out_r.iter_mut().zip(in_r.iter()).zip(thresholds_r)
+            {
+                *out_buf_r_sample = shaper.process(*in_buf_r_sample, *threshold);
+            }
+        }
+    }
+}
+
+fn clip_split(
+    (in_l, in_r): (&[f32], &[f32]),
+    (out_l, out_r): (&mut [f32], &mut [f32]),
+    (split_clip, (low_gain, high_gain)): (&mut [Crossover; 2], (f32, f32)),
+    (thresholds_l, thresholds_r): (&[f32], &[f32]),
+) {
+    let [crossover_l, crossover_r] = split_clip;
+
+    // Hard clip the lows, soft clip the highs L
+    for ((out_buf_l_sample, in_buf_l_sample), threshold) in
+        out_l.iter_mut().zip(in_l.iter()).zip(thresholds_l)
+    {
+        let (low, high) = crossover_l.split(*in_buf_l_sample);
+        *out_buf_l_sample =
+            low.clamp(-threshold, *threshold) * low_gain + soft_clip(high, *threshold) * high_gain;
+    }
+
+    // Hard clip the lows, soft clip the highs R
+    for ((out_buf_r_sample, in_buf_r_sample), threshold) in
+        out_r.iter_mut().zip(in_r.iter()).zip(thresholds_r)
+    {
+        let (low, high) = crossover_r.split(*in_buf_r_sample);
+        *out_buf_r_sample =
+            low.clamp(-threshold, *threshold) * low_gain + soft_clip(high, *threshold) * high_gain;
     }
+}
 
-    // Mix it R
-    for (out_buf_r_sample, in_buf_r_sample) in out_r.iter_mut().zip(in_r.iter()) {
+// How the 8-bitify stage rounds, each grid is given as steps per unit of level
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Quantizer {
+    // towards zero, 2^(bits-1)-1 steps each side, the original crunch
+    Truncate,
+    // no level at zero, used where truncating would leave nothing but silence
+    MidRise,
+    // an exact number of levels across -1..1, both ends included
+    Levels,
+}
+
+// the grid for the crush bits, or for the level count when one is set
+fn crush_grid(bits: f32, levels: f32) -> (f32, Quantizer) {
+    if levels >= 2.0 {
+        ((levels - 1.0) * 0.5, Quantizer::Levels)
+    } else if bits > MID_RISE_BITS {
+        (2.0_f32.powf(bits - 1.0) - 1.0, Quantizer::Truncate)
+    } else {
+        (2.0_f32.powf(bits - 1.0), Quantizer::MidRise)
+    }
+}
+
+// digital silence passes the mid-rise and level grids as is so an empty track doesn't turn into DC
+fn quantize(sample: f32, resolution: f32, quantizer: Quantizer) -> f32 {
+    match quantizer {
+        Quantizer::Truncate => (sample * resolution).trunc() / resolution,
+        _ if sample == 0.0 => 0.0,
+        // 1 bit keeps a +-half scale square
+        Quantizer::MidRise => ((sample * resolution).floor() + 0.5) / resolution,
+        Quantizer::Levels => ((sample + 1.0) * resolution).round() / resolution - 1.0,
+    }
+}
+
+// squeezes the range so quantizing afterwards keeps more resolution near zero
+fn mu_law_compress(sample: f32) -> f32 {
+    ((1.0 + MU_LAW_MU * sample.abs()).ln() / (1.0 + MU_LAW_MU).ln()).copysign(sample)
+}
+
+fn mu_law_expand(sample: f32) -> f32 {
+    (((1.0 + MU_LAW_MU).powf(sample.abs()) - 1.0) / MU_LAW_MU).copysign(sample)
+}
+
+// the fade and the step glide live in the state, so the stage can sit before or after the clipper
+fn lose_precision(
+    (out_l, out_r): (&mut [f32], &mut [f32]),
+    (bitify_amount, crush_resolution): (&mut f32, &mut f32),
+    sample_rate: f32,
+    is_on: bool,
+    ((target_resolution, quantizer), is_mu_law, stereo): ((f32, Quantizer), bool, CrushStereo),
+) {
+    // first block, start right at the setting
+    if *crush_resolution == 0.0 {
+        *crush_resolution = target_resolution;
+    }
+
+    // fully off, nothing to do
+    if !is_on && *bitify_amount == 0.0 {
+        *crush_resolution = target_resolution;
+        return;
+    }
+
+    let fade_step = 1000.0 / (MODE_FADE_MS * sample_rate);
+    let smooth = one_pole_coef(CRUSH_SMOOTH_MS, sample_rate);
+    let target = if is_on { 1.0 } else { 0.0 };
+    for (out_buf_l_sample, out_buf_r_sample) in out_l.iter_mut().zip(out_r.iter_mut()) {
+        *bitify_amount = if target > *bitify_amount {
+            (*bitify_amount + fade_step).min(target)
+        } else {
+            (*bitify_amount - fade_step).max(target)
+        };
+        *crush_resolution += (target_resolution - *crush_resolution) * smooth;
+
+        let (amount, resolution) = (*bitify_amount, *crush_resolution);
+        let crush = |sample: f32| {
+            if is_mu_law {
+                mu_law_expand(quantize(mu_law_compress(sample), resolution, quantizer))
+            } else {
+                quantize(sample, resolution, quantizer)
+            }
+        };
+        let (crushed_l, crushed_r) = match stereo {
+            CrushStereo::LeftRight => (crush(*out_buf_l_sample), crush(*out_buf_r_sample)),
+            // encode, crush the mid (and the side unless it's left clean), decode
+            CrushStereo::MidSide | CrushStereo::MidOnly => {
+                let mid = crush((*out_buf_l_sample + *out_buf_r_sample) * 0.5);
+                let side = (*out_buf_l_sample - *out_buf_r_sample) * 0.5;
+                let side = if stereo == CrushStereo::MidSide {
+                    crush(side)
+                } else {
+                    side
+                };
+                (mid + side, mid - side)
+            }
+        };
+        // weighted this way round so fully faded in is exactly the crushed sample
+        *out_buf_l_sample = crushed_l * amount + *out_buf_l_sample * (1.0 - amount);
+        *out_buf_r_sample = crushed_r * amount + *out_buf_r_sample * (1.0 - amount);
+    }
+}
+
+// Low-pass that only comes in while the clipper is driven hard, is_heavy decides per block
+fn safety_filter(
+    (out_l, out_r): (&mut [f32], &mut [f32]),
+    state: &mut DspState,
+    (is_on, is_heavy): (bool, bool),
+) {
+    if !is_on && state.safety_amount == 0.0 {
+        return;
+    }
+
+    let fade_step = 1000.0 / (MODE_FADE_MS * state.sample_rate);
+    let target = if is_on && is_heavy { 1.0 } else { 0.0 };
+    let [filter_l, filter_r] = &mut state.safety_filter;
+    for (out_buf_l_sample, out_buf_r_sample) in out_l.iter_mut().zip(out_r.iter_mut()) {
+        state.safety_amount = if target > state.safety_amount {
+            (state.safety_amount + fade_step).min(target)
+        } else {
+            (state.safety_amount - fade_step).max(target)
+        };
+        // keep the filters running so fading in doesn't start from stale memory
+        let filtered_l = filter_l.process(*out_buf_l_sample);
+        let filtered_r = filter_r.process(*out_buf_r_sample);
+        *out_buf_l_sample += (filtered_l - *out_buf_l_sample) * state.safety_amount;
+        *out_buf_r_sample += (filtered_r - *out_buf_r_sample) * state.safety_amount;
+    }
+}
+
+// fades from `from` into `to` in place, returns how far the fade got
+fn crossfade(
+    (from_l, from_r): (&[f32], &[f32]),
+    (to_l, to_r): (&mut [f32], &mut [f32]),
+    mut position: f32,
+    step: f32,
+) -> f32 {
+    for ((to_l_sample, to_r_sample), (from_l_sample, from_r_sample)) in to_l
+        .iter_mut()
+        .zip(to_r.iter_mut())
+        .zip(from_l.iter().zip(from_r.iter()))
+    {
+        position = (position + step).min(1.0);
+        *to_l_sample = from_l_sample + (*to_l_sample - from_l_sample) * position;
+        *to_r_sample = from_r_sample + (*to_r_sample - from_r_sample) * position;
+    }
+    position
+}
+
+// tanh saturation, top end roll-off and slow compression, all scaled by `amount`
+fn tape((out_l, out_r): (&mut [f32], &mut [f32]), state: &mut DspState, amount: f32) {
+    let cutoff = TAPE_CUTOFF_MAX * (TAPE_CUTOFF_MIN / TAPE_CUTOFF_MAX).powf(amount);
+    if cutoff != state.tape_cutoff {
+        for filter in &mut state.tape_filter {
+            filter.set_low_pass(cutoff, FRAC_1_SQRT_2, state.sample_rate);
+        }
+        state.tape_cutoff = cutoff;
+    }
+
+    let drive = 1.0 + amount * TAPE_DRIVE_MAX;
+    let ratio = 1.0 + amount * TAPE_RATIO_MAX;
+    let threshold = to_linear(TAPE_COMP_THRESHOLD_DB);
+    let attack = one_pole_coef(TAPE_ATTACK_MS, state.sample_rate);
+    let release = one_pole_coef(TAPE_RELEASE_MS, state.sample_rate);
+    let [filter_l, filter_r] = &mut state.tape_filter;
+
+    for (out_buf_l_sample, out_buf_r_sample) in out_l.iter_mut().zip(out_r.iter_mut()) {
+        // linked level detection so the stereo image doesn't wander
+        let level = out_buf_l_sample.abs().max(out_buf_r_sample.abs());
+        let coef = if level > state.tape_env {
+            attack
+        } else {
+            release
+        };
+        state.tape_env += (level - state.tape_env) * coef;
+        let comp_gain = if state.tape_env > threshold {
+            (threshold / state.tape_env).powf(1.0 - 1.0 / ratio)
+        } else {
+            1.0
+        };
+
+        // unity gain for small signals, only the peaks bend
+        let l = *out_buf_l_sample * comp_gain;
+        let r = *out_buf_r_sample * comp_gain;
+        let l = l + ((l * drive).tanh() / drive - l) * amount;
+        let r = r + ((r * drive).tanh() / drive - r) * amount;
+        *out_buf_l_sample = filter_l.process(l);
+        *out_buf_r_sample = filter_r.process(r);
+    }
+}
+
+// gentle stereo linked compressor that evens out the level going into the clipper
+// attack and release are one-pole coefficients, the reduction carries over between blocks
+// the sidechain filter, when given, only shapes what the detector hears
+fn glue(
+    (buf_l, buf_r): (&mut [f32], &mut [f32]),
+    (reduction, hold_left): (&mut f32, &mut u32),
+    sidechain: Option<&mut [Biquad; 2]>,
+    (threshold, ratio): (f32, f32),
+    (attack, release, hold): (f32, f32, u32),
+) {
+    let threshold_db = to_db(threshold);
+    let mut sidechain = sidechain;
+
+    for (buf_l_sample, buf_r_sample) in buf_l.iter_mut().zip(buf_r.iter_mut()) {
+        let (detect_l, detect_r) = match sidechain.as_deref_mut() {
+            Some([filter_l, filter_r]) => (
+                filter_l.process(*buf_l_sample),
+                filter_r.process(*buf_r_sample),
+            ),
+            None => (*buf_l_sample, *buf_r_sample),
+        };
+        let over = to_db(detect_l.abs().max(detect_r.abs())) - threshold_db;
+        let target = if over > 0.0 {
+            over * (1.0 - 1.0 / ratio)
+        } else {
+            0.0
+        };
+        // every hit over the threshold starts the hold over, so close hits don't let it chatter
+        if over > 0.0 {
+            *hold_left = hold;
+        }
+        if target > *reduction {
+            *reduction += (target - *reduction) * attack;
+        } else if *hold_left > 0 {
+            *hold_left -= 1;
+        } else {
+            *reduction += (target - *reduction) * release;
+        }
+
+        let gain = to_linear(-*reduction);
+        *buf_l_sample *= gain;
+        *buf_r_sample *= gain;
+    }
+}
+
+// boosts the attack of each hit by how far the fast follower runs ahead of the slow one
+fn transient(
+    (buf_l, buf_r): (&mut [f32], &mut [f32]),
+    [fast_env, slow_env]: &mut [f32; 2],
+    amount: f32,
+    (fast_attack, slow_attack, release): (f32, f32, f32),
+) {
+    // the amount scales the ceiling too, or every onset would hit it right away
+    let gain_max = to_linear(TRANSIENT_GAIN_MAX_DB * amount);
+
+    for (buf_l_sample, buf_r_sample) in buf_l.iter_mut().zip(buf_r.iter_mut()) {
+        // linked so both sides get the same punch
+        let level = buf_l_sample.abs().max(buf_r_sample.abs());
+        let fast_coef = if level > *fast_env {
+            fast_attack
+        } else {
+            release
+        };
+        let slow_coef = if level > *slow_env {
+            slow_attack
+        } else {
+            release
+        };
+        *fast_env += (level - *fast_env) * fast_coef;
+        *slow_env += (level - *slow_env) * slow_coef;
+
+        let gain = if *slow_env > 0.0 {
+            (*fast_env / *slow_env).powf(amount).clamp(1.0, gain_max)
+        } else {
+            1.0
+        };
+        *buf_l_sample *= gain;
+        *buf_r_sample *= gain;
+    }
+}
+
+// filters that were switched off sat idle with stale memories, so they start over when
+// they come back on, `setting` is the frequency or gain they were last set to, 0 while off
+fn wake_filters<'a>(setting: f32, filters: impl IntoIterator<Item = &'a mut Biquad>) {
+    if setting == 0.0 {
+        for filter in filters {
+            filter.reset();
+        }
+    }
+}
+
+// keeps both shelves in step with the emphasis setting
+fn set_emphasis(state: &mut DspState, gain_db: f32) {
+    if gain_db == state.emphasis_db {
+        return;
+    }
+    wake_filters(
+        state.emphasis_db,
+        state
+            .emphasis_pre
+            .iter_mut()
+            .chain(state.emphasis_post.iter_mut()),
+    );
+    for filter in &mut state.emphasis_pre {
+        filter.set_high_shelf(EMPHASIS_FREQ, FRAC_1_SQRT_2, gain_db, state.sample_rate);
+    }
+    for filter in &mut state.emphasis_post {
+        filter.set_high_shelf(EMPHASIS_FREQ, FRAC_1_SQRT_2, -gain_db, state.sample_rate);
+    }
+    state.emphasis_db = gain_db;
+}
+
+fn shelve((buf_l, buf_r): (&mut [f32], &mut [f32]), [filter_l, filter_r]: &mut [Biquad; 2]) {
+    for buf_l_sample in buf_l.iter_mut() {
+        *buf_l_sample = filter_l.process(*buf_l_sample);
+    }
+    for buf_r_sample in buf_r.iter_mut() {
+        *buf_r_sample = filter_r.process(*buf_r_sample);
+    }
+}
+
+// records the output into the ring, or loops the ring while frozen
+fn freeze(
+    (out_l, out_r): (&mut [f32], &mut [f32]),
+    state: &mut DspState,
+    is_on: bool,
+    fade_step: f32,
+) {
+    let len = state.freeze_buf[0].len();
+    if len == 0 {
+        return;
+    }
+    let loop_fade = ((FREEZE_LOOP_FADE_MS / 1000.0 * state.sample_rate) as usize).clamp(1, len / 2);
+
+    // just engaged, the oldest sample in the ring starts the loop
+    if is_on && !state.is_frozen {
+        state.freeze_start = state.freeze_write;
+        state.freeze_read = 0;
+    }
+    state.is_frozen = is_on;
+
+    let target = if is_on { 1.0 } else { 0.0 };
+    let [freeze_buf_l, freeze_buf_r] = &mut state.freeze_buf;
+    for (out_buf_l_sample, out_buf_r_sample) in out_l.iter_mut().zip(out_r.iter_mut()) {
+        state.freeze_amount = if target > state.freeze_amount {
+            (state.freeze_amount + fade_step).min(target)
+        } else {
+            (state.freeze_amount - fade_step).max(target)
+        };
+
+        // live, keep recording
+        if state.freeze_amount == 0.0 {
+            freeze_buf_l[state.freeze_write] = *out_buf_l_sample;
+            freeze_buf_r[state.freeze_write] = *out_buf_r_sample;
+            state.freeze_write = (state.freeze_write + 1) % len;
+            continue;
+        }
+
+        let position = state.freeze_read;
+        let index = (state.freeze_start + position) % len;
+        let (mut frozen_l, mut frozen_r) = (freeze_buf_l[index], freeze_buf_r[index]);
+        // the end of the loop fades into its start, playback then carries on after the fade
+        if position >= len - loop_fade {
+            let wrapped = position - (len - loop_fade);
+            let fade = wrapped as f32 / loop_fade as f32;
+            let index = (state.freeze_start + wrapped) % len;
+            frozen_l += (freeze_buf_l[index] - frozen_l) * fade;
+            frozen_r += (freeze_buf_r[index] - frozen_r) * fade;
+        }
+        state.freeze_read = if position + 1 >= len {
+            loop_fade
+        } else {
+            position + 1
+        };
+
+        let amount = state.freeze_amount;
+        *out_buf_l_sample += (frozen_l - *out_buf_l_sample) * amount;
+        *out_buf_r_sample += (frozen_r - *out_buf_r_sample) * amount;
+    }
+}
+
+// tape speed wobble, reads a ring buffer at a moving fractional delay
+fn wow_flutter(
+    (out_l, out_r): (&mut [f32], &mut [f32]),
+    state: &mut DspState,
+    (wow, flutter): (f32, f32),
+) {
+    let len = state.wow_buf[0].len();
+    if len == 0
+        || (wow == 0.0 && flutter == 0.0 && state.wow_depth == 0.0 && state.flutter_depth == 0.0)
+    {
+        return;
+    }
+
+    let smooth = one_pole_coef(WOW_SMOOTH_MS, state.sample_rate);
+    let (wow_target, flutter_target) = (wow * WOW_DEPTH_MS, flutter * FLUTTER_DEPTH_MS);
+    let samples_per_ms = state.sample_rate / 1000.0;
+    let [wow_buf_l, wow_buf_r] = &mut state.wow_buf;
+    for (out_buf_l_sample, out_buf_r_sample) in out_l.iter_mut().zip(out_r.iter_mut()) {
+        state.wow_depth += (wow_target - state.wow_depth) * smooth;
+        state.flutter_depth += (flutter_target - state.flutter_depth) * smooth;
+        state.wow_phase = (state.wow_phase + WOW_RATE_HZ / state.sample_rate).fract();
+        state.flutter_phase = (state.flutter_phase + FLUTTER_RATE_HZ / state.sample_rate).fract();
+
+        wow_buf_l[state.wow_write] = *out_buf_l_sample;
+        wow_buf_r[state.wow_write] = *out_buf_r_sample;
+
+        let delay_ms = state.wow_depth * (1.0 - (TAU * state.wow_phase).cos())
+            + state.flutter_depth * (1.0 - (TAU * state.flutter_phase).cos());
+        let delay = (delay_ms * samples_per_ms).clamp(0.0, (len - 2) as f32);
+        let back = delay as usize;
+        let frac = delay - back as f32;
+        let newer = (state.wow_write + len - back) % len;
+        let older = (newer + len - 1) % len;
+        *out_buf_l_sample = wow_buf_l[newer] + (wow_buf_l[older] - wow_buf_l[newer]) * frac;
+        *out_buf_r_sample = wow_buf_r[newer] + (wow_buf_r[older] - wow_buf_r[newer]) * frac;
+
+        state.wow_write = (state.wow_write + 1) % len;
+    }
+
+    // settled at off, skip the line until it's turned up again
+    if wow == 0.0 && state.wow_depth < 1e-6 {
+        state.wow_depth = 0.0;
+    }
+    if flutter == 0.0 && state.flutter_depth < 1e-6 {
+        state.flutter_depth = 0.0;
+    }
+}
+
+// ramps the block up from silence, returns how far the fade got
+fn fade_in((out_l, out_r): (&mut [f32], &mut [f32]), mut position: f32, step: f32) -> f32 {
+    for (out_buf_l_sample, out_buf_r_sample) in out_l.iter_mut().zip(out_r.iter_mut()) {
+        position = (position + step).min(1.0);
+        *out_buf_l_sample *= position;
+        *out_buf_r_sample *= position;
+    }
+    position
+}
+
+fn clip_mid_side(
+    (out_l, out_r): (&mut [f32], &mut [f32]),
+    (mid_threshold, side_threshold): (f32, f32),
+    is_side_limit: bool,
+) {
+    // encode, clip mid and side on their own, decode
+    for (out_buf_l_sample, out_buf_r_sample) in out_l.iter_mut().zip(out_r.iter_mut()) {
+        let mid = (*out_buf_l_sample + *out_buf_r_sample) * 0.5;
+        let side = (*out_buf_l_sample - *out_buf_r_sample) * 0.5;
+        let mid = mid.clamp(-mid_threshold, mid_threshold);
+        // the limiter leans into the side threshold instead of squaring the overs off
+        let side = if is_side_limit {
+            soft_clip(side, side_threshold)
+        } else {
+            side.clamp(-side_threshold, side_threshold)
+        };
+        *out_buf_l_sample = mid + side;
+        *out_buf_r_sample = mid - side;
+    }
+}
+
+fn mix(
+    (in_l, in_r): (&[f32], &[f32]),
+    (out_l, out_r): (&mut [f32], &mut [f32]),
+    mix_smooth: &mut SmoothedParam,
+    mix: f32,
+) {
+    // Mix it, both sides follow the same glide
+    for ((out_buf_l_sample, out_buf_r_sample), (in_buf_l_sample, in_buf_r_sample)) in out_l
+        .iter_mut()
+        .zip(out_r.iter_mut())
+        .zip(in_l.iter().zip(in_r.iter()))
+    {
+        let mix = mix_smooth.next(mix);
+        *out_buf_l_sample = (*out_buf_l_sample * mix) + ((1.0 - mix) * in_buf_l_sample);
         *out_buf_r_sample = (*out_buf_r_sample * mix) + ((1.0 - mix) * in_buf_r_sample);
     }
 }
 
-fn remove_silence((out_buf_l, out_buf_r): (&mut [f32], &mut [f32])) {
+// gives the dry signal the roll-off the tape put on the wet one, so moving the mix
+// changes how much is processed and not how bright it sounds
+// brings the dry signal up or down to the loudness of the wet one, so comparing the two
+// isn't won by whichever is louder
+fn level_match(
+    (in_l, in_r): (&mut [f32], &mut [f32]),
+    (out_l, out_r): (&[f32], &[f32]),
+    state: &mut DspState,
+) {
+    let coef = one_pole_coef(LEVEL_MATCH_MS, state.sample_rate);
+    let makeup_max = to_linear(LEVEL_MATCH_DB_MAX);
+    let [wet_power, dry_power] = &mut state.level_match_power;
+
+    for ((in_buf_l_sample, in_buf_r_sample), (out_buf_l_sample, out_buf_r_sample)) in in_l
+        .iter_mut()
+        .zip(in_r.iter_mut())
+        .zip(out_l.iter().zip(out_r.iter()))
+    {
+        let wet = (out_buf_l_sample * out_buf_l_sample + out_buf_r_sample * out_buf_r_sample) * 0.5;
+        let dry = (*in_buf_l_sample * *in_buf_l_sample + *in_buf_r_sample * *in_buf_r_sample) * 0.5;
+        *wet_power += (wet - *wet_power) * coef;
+        *dry_power += (dry - *dry_power) * coef;
+        // nothing to match against until the dry side has been heard
+        let makeup = if *dry_power > 0.0 {
+            (*wet_power / *dry_power)
+                .sqrt()
+                .clamp(1.0 / makeup_max, makeup_max)
+        } else {
+            1.0
+        };
+        *in_buf_l_sample *= makeup;
+        *in_buf_r_sample *= makeup;
+    }
+}
+
+fn dry_match((in_l, in_r): (&mut [f32], &mut [f32]), state: &mut DspState) {
+    if state.tape_cutoff != state.dry_match_cutoff {
+        wake_filters(state.dry_match_cutoff, &mut state.dry_match_filter);
+        for filter in &mut state.dry_match_filter {
+            filter.set_low_pass(state.tape_cutoff, FRAC_1_SQRT_2, state.sample_rate);
+        }
+        state.dry_match_cutoff = state.tape_cutoff;
+    }
+
+    let [filter_l, filter_r] = &mut state.dry_match_filter;
+    for (in_buf_l_sample, in_buf_r_sample) in in_l.iter_mut().zip(in_r.iter_mut()) {
+        *in_buf_l_sample = filter_l.process(*in_buf_l_sample);
+        *in_buf_r_sample = filter_r.process(*in_buf_r_sample);
+    }
+}
+
+// Parallel mix, the full dry signal with only the high end of the wet one on top,
+// so the low end stays exactly as it came in
+fn parallel_mix(
+    (in_l, in_r): (&[f32], &[f32]),
+    (out_l, out_r): (&mut [f32], &mut [f32]),
+    state: &mut DspState,
+    (mix, freq): (f32, f32),
+) {
+    if freq != state.wet_freq {
+        wake_filters(state.wet_freq, &mut state.wet_filter);
+        for filter in &mut state.wet_filter {
+            filter.set_high_pass(freq, FRAC_1_SQRT_2, state.sample_rate);
+        }
+        state.wet_freq = freq;
+    }
+    let [filter_l, filter_r] = &mut state.wet_filter;
+
+    for ((out_buf_l_sample, out_buf_r_sample), (in_buf_l_sample, in_buf_r_sample)) in out_l
+        .iter_mut()
+        .zip(out_r.iter_mut())
+        .zip(in_l.iter().zip(in_r.iter()))
+    {
+        let mix = state.mix_smooth.next(mix);
+        *out_buf_l_sample = in_buf_l_sample + filter_l.process(*out_buf_l_sample) * mix;
+        *out_buf_r_sample = in_buf_r_sample + filter_r.process(*out_buf_r_sample) * mix;
+    }
+}
+
+fn route((buf_l, buf_r): (&mut [f32], &mut [f32]), mode: RoutingMode) {
+    match mode {
+        RoutingMode::Stereo => (),
+        RoutingMode::Swap => buf_l.swap_with_slice(buf_r),
+        RoutingMode::LeftOnly => buf_r.copy_from_slice(buf_l),
+        RoutingMode::RightOnly => buf_l.copy_from_slice(buf_r),
+    }
+}
+
+fn trim((buf_l, buf_r): (&mut [f32], &mut [f32]), trim_smooth: &mut SmoothedParam, trim: f32) {
+    for (buf_l_sample, buf_r_sample) in buf_l.iter_mut().zip(buf_r.iter_mut()) {
+        let trim = trim_smooth.next(trim);
+        *buf_l_sample *= trim;
+        *buf_r_sample *= trim;
+    }
+}
+
+fn invert((buf_l, buf_r): (&mut [f32], &mut [f32]), (invert_l, invert_r): (bool, bool)) {
+    // Flip polarity L
+    if invert_l {
+        for buf_l_sample in &mut *buf_l {
+            *buf_l_sample = -*buf_l_sample;
+        }
+    }
+
+    // Flip polarity R
+    if invert_r {
+        for buf_r_sample in &mut *buf_r {
+            *buf_r_sample = -*buf_r_sample;
+        }
+    }
+}
+
+fn mono_maker((out_l, out_r): (&mut [f32], &mut [f32]), state: &mut DspState, freq: f32) {
+    if freq != state.mono_maker_freq {
+        for crossover in &mut state.mono_maker {
+            crossover.set_frequency(freq, state.sample_rate);
+        }
+        state.mono_maker_freq = freq;
+    }
+
+    let [crossover_l, crossover_r] = &mut state.mono_maker;
+
+    // Sum the lows to mono, keep the highs stereo
+    for (out_buf_l_sample, out_buf_r_sample) in out_l.iter_mut().zip(out_r.iter_mut()) {
+        let (low_l, high_l) = crossover_l.split(*out_buf_l_sample);
+        let (low_r, high_r) = crossover_r.split(*out_buf_r_sample);
+        let low_mono = (low_l + low_r) * 0.5;
+        *out_buf_l_sample = low_mono + high_l;
+        *out_buf_r_sample = low_mono + high_r;
+    }
+}
+
+fn trance_gate(
+    (out_l, out_r): (&mut [f32], &mut [f32]),
+    state: &mut DspState,
+    (steps, step_beats, curve): (&[bool; GATE_STEPS], f64, GateCurve),
+    transport: &Transport,
+) {
+    let beats_per_sample = transport.tempo / 60.0 / state.sample_rate as f64;
+    let smooth = one_pole_coef(GATE_SMOOTH_MS, state.sample_rate);
+    let ramp_step = 1000.0 / (GATE_SMOOTH_MS * state.sample_rate);
+
+    for (index, (out_buf_l_sample, out_buf_r_sample)) in
+        out_l.iter_mut().zip(out_r.iter_mut()).enumerate()
+    {
+        // stay open while the transport is stopped
+        let target = if transport.is_playing {
+            let ppq = transport.ppq_pos + index as f64 * beats_per_sample - state.gate_anchor;
+            let step = (ppq / step_beats).floor().rem_euclid(GATE_STEPS as f64) as usize;
+            if steps[step] {
+                1.0
+            } else {
+                0.0
+            }
+        } else {
+            1.0
+        };
+        state.gate_gain = match curve {
+            GateCurve::Exponential => state.gate_gain + (target - state.gate_gain) * smooth,
+            GateCurve::Linear => {
+                state.gate_gain + (target - state.gate_gain).clamp(-ramp_step, ramp_step)
+            }
+        };
+        *out_buf_l_sample *= state.gate_gain;
+        *out_buf_r_sample *= state.gate_gain;
+    }
+}
+
+fn dither((out_l, out_r): (&mut [f32], &mut [f32]), state: &mut DspState) {
+    let scale = (1 << (DITHER_BITS - 1)) as f32;
+    let rng = &mut state.dither_rng;
+
+    for (buf, error) in [out_l, out_r]
+        .into_iter()
+        .zip(state.dither_error.iter_mut())
+    {
+        for sample in buf.iter_mut() {
+            // 2nd order error feedback pushes the noise up towards nyquist
+            let shaped = *sample - 2.0 * error[0] + error[1];
+            // triangular pdf, +-1 lsb
+            let noise = rng.next_f32() - rng.next_f32();
+            let quantized = (shaped * scale + noise).round() / scale;
+            error[1] = error[0];
+            error[0] = quantized - shaped;
+            *sample = quantized;
+        }
+    }
+}
+
+// Zero a channel once it has stayed below the threshold for more than
+// SILENT_THRESHOLD_COUNT samples in a row, any louder sample opens it again.
+// Runs on the signal picked by the Silence Gate param, never on the raw output buffer.
+// Linked, both channels share the left counter and only close when both are quiet
+fn remove_silence(
+    (out_buf_l, out_buf_r): (&mut [f32], &mut [f32]),
+    silence_counter: &mut [i32; 2],
+    (is_legacy, is_linked): (bool, bool),
+) {
+    if is_legacy {
+        *silence_counter = [0; 2];
+        remove_silence_legacy((out_buf_l, out_buf_r));
+        return;
+    }
+
+    let [silence_counter_l, silence_counter_r] = silence_counter;
+    if is_linked {
+        *silence_counter_r = 0;
+        for (out_buf_l_sample, out_buf_r_sample) in out_buf_l.iter_mut().zip(out_buf_r.iter_mut()) {
+            if out_buf_l_sample.abs().max(out_buf_r_sample.abs()) < SILENT_THRESHOLD_DB {
+                *silence_counter_l = silence_counter_l.saturating_add(1);
+                if *silence_counter_l > SILENT_THRESHOLD_COUNT {
+                    *out_buf_l_sample = 0.0;
+                    *out_buf_r_sample = 0.0;
+                }
+            } else {
+                *silence_counter_l = 0;
+            }
+        }
+        return;
+    }
+
+    // Ignore silence if loudness < threshold L
+    for out_buf_l_sample in &mut *out_buf_l {
+        if out_buf_l_sample.abs() < SILENT_THRESHOLD_DB {
+            *silence_counter_l = silence_counter_l.saturating_add(1);
+            if *silence_counter_l > SILENT_THRESHOLD_COUNT {
+                *out_buf_l_sample = 0.0;
+            }
+        } else {
+            *silence_counter_l = 0;
+        }
+    }
+
+    // Ignore silence if loudness < threshold R
+    for out_buf_r_sample in &mut *out_buf_r {
+        if out_buf_r_sample.abs() < SILENT_THRESHOLD_DB {
+            *silence_counter_r = silence_counter_r.saturating_add(1);
+            if *silence_counter_r > SILENT_THRESHOLD_COUNT {
+                *out_buf_r_sample = 0.0;
+            }
+        } else {
+            *silence_counter_r = 0;
+        }
+    }
+}
+
+// The gate as it used to be, kept for sessions built around its sound: the count
+// starts over every block, never resets on louder samples, and compares the raw
+// sample so negative samples always count as quiet
+fn remove_silence_legacy((out_buf_l, out_buf_r): (&mut [f32], &mut [f32])) {
     // Set silence sample counter
     let mut silence_counter_l: i32 = 0;
     let mut silence_counter_r: i32 = 0;
@@ -47,53 +1396,1313 @@ fn remove_silence((out_buf_l, out_buf_r): (&mut [f32], &mut [f32])) {
 }
 
 pub fn process(
-    in_buf_l: &[f32],
-    in_buf_r: &[f32],
+    in_buf_l: &mut [f32],
+    in_buf_r: &mut [f32],
     out_buf_l: &mut [f32],
     out_buf_r: &mut [f32],
     params: &EffectParams,
+    state: &mut DspState,
+    transport: &Transport,
 ) {
     // get param
-    let clamp_range = params.clamp_threshold.get();
-    let lose_precision = params.lose_precision.get();
-    let mix_level = params.mix.get();
+    let (clamp_range, gain, mix_level) = params.amount_shaped();
+    let input_trim = to_linear(params.input_trim.get());
+    let is_lose_precision = params.lose_precision.get() > 0.5;
+    let smoothing_ms = params.smoothing_ms.get();
+    let threshold_smoothing_ms = params.threshold_smoothing_ms.get();
+    let is_dry_bleed = params.dry_bleed.get() > 0.5;
+    let is_parallel = params.parallel.get() > 0.5;
+    let is_compare = params.compare.get() > 0.5;
+    let is_level_match = params.level_match.get() > 0.5;
+    let is_dry_match = params.dry_match.get() > 0.5;
+    let gain_mode = GainMode::from_index(params.gain_mode.get());
+    let analog_drift = params.analog_drift.get();
+    let stage_order = StageOrder::from_index(params.stage_order.get());
+    let test_signal = TestSignalMode::from_index(params.test_signal.get());
+    let test_freq = params.test_freq.get();
+    let wet_hpf = params.wet_hpf.get();
+    let routing_mode = RoutingMode::from_index(params.routing_mode.get());
+    let invert_l = params.invert_l.get() > 0.5;
+    let invert_r = params.invert_r.get() > 0.5;
+    let output_ceiling = params.output_ceiling.get() > 0.5;
+    let is_mono_maker = params.mono_maker.get() > 0.5;
+    let mono_maker_freq = params.mono_maker_freq.get();
+    let clip_mode = ClipMode::from_index(params.clip_mode.get());
+    let curve = Curve::from_index(params.curve.get());
+    let is_output_dither = params.output_dither.get() > 0.5;
+    let crush_bits = crush_to_bits(params.crush.get());
+    let quant_levels = params.quant_levels.get();
+    let is_mu_law = params.mu_law.get() > 0.5;
+    let crush_stereo = CrushStereo::from_index(params.crush_stereo.get());
+    let is_safety_lpf = params.safety_lpf.get() > 0.5;
+    let is_true_peak_meter = params.true_peak_meter.get() > 0.5;
+    // lite UI, nothing is measured for an editor that shows no meters
+    let is_metering = params.lite_ui.get() < 0.5;
+    let is_gate = params.gate.get() > 0.5;
+    let gate_step_beats = gate_rate_beats(params.gate_rate.get());
+    let gate_curve = GateCurve::from_index(params.gate_curve.get());
+    let gate_steps: [bool; GATE_STEPS] =
+        std::array::from_fn(|step| params.gate_steps[step].get() > 0.5);
+    let is_clip2 = params.clip2.get() > 0.5;
+    let clip2_mode = ClipMode::from_index(params.clip2_mode.get());
+    let clip2_threshold = params.clip2_threshold.get();
+    let is_freeze = params.freeze.get() > 0.5;
+    let is_check_mono = params.check_mono.load(Ordering::Relaxed);
+    let solo_low = params.solo_low.load(Ordering::Relaxed);
+    let solo_high = params.solo_high.load(Ordering::Relaxed);
+    let seed = params.seed.get() as u32;
+    let silence_position = SilencePosition::from_index(params.silence_position.get());
+    let is_legacy_silence = params.legacy_silence.get() > 0.5;
+    let is_silence_linked = params.silence_link.get() > 0.5;
+    let is_power_save = params.power_save.get() > 0.5;
+    let tape_amount = params.tape.get();
+    let wow = params.wow.get();
+    let flutter = params.flutter.get();
+    let is_glue = params.glue.get() > 0.5;
+    let glue_threshold = params.glue_threshold.get();
+    let glue_ratio = params.glue_ratio.get();
+    let glue_attack = params.glue_attack.get();
+    let glue_release = params.glue_release.get();
+    let glue_hold = params.glue_hold.get();
+    let sidechain_hpf = params.sidechain_hpf.get();
+    let emphasis_db = params.emphasis.get() * EMPHASIS_DB_MAX;
+    let transient_amount = params.transient.get();
+    let sat_floor_amount = params.sat_floor.get();
+    let is_ms_clip = params.ms_clip.get() > 0.5;
+    let mid_threshold = params.mid_threshold.get();
+    let side_threshold = params.side_threshold.get();
+    let is_side_limit = params.side_limit.get() > 0.5;
+    let fade_step = 1000.0 / (MODE_FADE_MS * state.sample_rate);
+
+    if params.panic.swap(false, Ordering::Relaxed) {
+        state.panic();
+    }
+    // counted for the editor, printing from here could block the audio thread
+    if state.sanitize() {
+        params.meters.state_resets.fetch_add(1, Ordering::Relaxed);
+    }
+    state.set_seed(seed);
+    state.set_smoothing_time(smoothing_ms, threshold_smoothing_ms);
+
+    // the test signal takes the place of whatever the host sent in
+    if test_signal != TestSignalMode::Off {
+        state.test_signal.fill(
+            (in_buf_l, in_buf_r),
+            (test_signal, test_freq),
+            state.sample_rate,
+        );
+    }
+
+    // route the channels before anything else, so invert L/R act on the routed sides
+    // and the dry signal is routed the same way as the wet one
+    route((in_buf_l, in_buf_r), routing_mode);
 
-    // remove silence
-    // return early if silent to avoid unnecessary processing
-    remove_silence((out_buf_l, out_buf_r));
+    // invert polarity next so every later stage (and the dry signal) sees it
+    invert((in_buf_l, in_buf_r), (invert_l, invert_r));
 
-    // Clamp L
-    for (index, in_buf_l_sample) in in_buf_l.iter().enumerate() {
-        out_buf_l[index] = in_buf_l_sample.clamp(-clamp_range, clamp_range);
+    // Input trim, gain staging for the whole plugin so the dry signal follows it too
+    trim((in_buf_l, in_buf_r), &mut state.trim_smooth, input_trim);
+    if is_metering {
+        params.meters.write_input((in_buf_l, in_buf_r));
     }
 
-    // Clamp R
-    for (index, in_buf_r_sample) in in_buf_r.iter().enumerate() {
-        out_buf_r[index] = in_buf_r_sample.clamp(-clamp_range, clamp_range);
+    // Power save, separate from the gate and remove_silence: once the input has been
+    // silent for a while, skip the whole chain until signal returns. The state is left as
+    // it was, the filters and delays have rung out by then, while the gate's anchor, the
+    // freeze loop and the wow line carry on from where they were
+    let silent_level = to_linear(POWER_SAVE_THRESHOLD_DB);
+    // a held freeze keeps playing over silence
+    let is_silent = block_peak(in_buf_l) < silent_level && block_peak(in_buf_r) < silent_level;
+    if is_power_save && is_silent && !is_freeze {
+        state.silent_samples = state.silent_samples.saturating_add(in_buf_l.len());
+    } else {
+        if state.is_sleeping {
+            state.is_sleeping = false;
+            state.wake_fade = 0.0;
+        }
+        state.silent_samples = 0;
+    }
+    if !state.is_sleeping
+        && state.silent_samples as f32 > POWER_SAVE_MS / 1000.0 * state.sample_rate
+    {
+        state.is_sleeping = true;
+    }
+    if state.is_sleeping {
+        out_buf_l.fill(0.0);
+        out_buf_r.fill(0.0);
+        if is_metering {
+            params
+                .meters
+                .write_clip((out_buf_l, out_buf_r), (out_buf_l, out_buf_r), clamp_range);
+            params.meters.envelope.set(0.0);
+            params.meters.write_output((out_buf_l, out_buf_r), 0.0);
+        }
+        return;
     }
 
-    // gain
-    for out_buf_l_sample in &mut *out_buf_l {
-        *out_buf_l_sample *= params.gain.get();
+    // Remove silence on the clean input, the output buffer still holds whatever the host
+    // left in it at this point so the gate has to look at the input instead
+    if silence_position == SilencePosition::PreClip {
+        remove_silence(
+            (in_buf_l, in_buf_r),
+            &mut state.silence_counter,
+            (is_legacy_silence, is_silence_linked),
+        );
     }
 
-    for out_buf_r_sample in &mut *out_buf_r {
-        *out_buf_r_sample *= params.gain.get();
+    // Clamp, a mode switch keeps the old mode running while fading over
+    if clip_mode != state.clip_mode {
+        if clip_mode == ClipMode::Split {
+            for crossover in &mut state.split_clip {
+                crossover.reset();
+            }
+        }
+        state.clip_mode_prev = state.clip_mode;
+        state.clip_mode = clip_mode;
+        state.clip_fade = 0.0;
+    }
+
+    // the block buffers are sized in set_block_size, a host going past what it announced
+    // there is the only time this allocates
+    let block_len = in_buf_l.len();
+    if state.gain_buf.len() < block_len {
+        state.set_block_size(block_len);
+    }
+
+    // the gain's glide for the whole block, shared by the drive and the output gain
+    state
+        .gain_smooth
+        .fill(&mut state.gain_buf[..block_len], gain);
+
+    // Drive, glue, transient emphasis, pre-emphasis and a crush ahead of the clipper work on
+    // a copy for the clipper so the dry signal stays untouched
+    let is_drive = gain_mode != GainMode::Output;
+    let is_emphasis = emphasis_db > 0.0;
+    let is_transient = transient_amount > 0.0;
+    let is_crush_first = stage_order == StageOrder::CrushFirst;
+    let is_clip_in_copy = is_drive || is_glue || is_transient || is_emphasis || is_crush_first;
+    if is_clip_in_copy {
+        let [clip_in_buf_l, clip_in_buf_r] = &mut state.clip_in_buf;
+        clip_in_buf_l[..block_len].copy_from_slice(in_buf_l);
+        clip_in_buf_r[..block_len].copy_from_slice(in_buf_r);
+    }
+
+    // Drive, the gain pushes into everything ahead of the clipper
+    if is_drive {
+        let [clip_in_buf_l, clip_in_buf_r] = &mut state.clip_in_buf;
+        for ((clip_in_l_sample, clip_in_r_sample), gain) in clip_in_buf_l
+            .iter_mut()
+            .zip(clip_in_buf_r.iter_mut())
+            .zip(&state.gain_buf)
+        {
+            *clip_in_l_sample *= gain;
+            *clip_in_r_sample *= gain;
+        }
+    }
+
+    // Glue, even out the dynamics so the clipper bites more consistently
+    if is_glue {
+        let is_sidechain_hpf = sidechain_hpf > SIDECHAIN_HPF_MIN;
+        if is_sidechain_hpf && sidechain_hpf != state.sidechain_freq {
+            wake_filters(state.sidechain_freq, &mut state.sidechain_filter);
+            for filter in &mut state.sidechain_filter {
+                filter.set_high_pass(sidechain_hpf, FRAC_1_SQRT_2, state.sample_rate);
+            }
+            state.sidechain_freq = sidechain_hpf;
+        } else if !is_sidechain_hpf {
+            state.sidechain_freq = 0.0;
+        }
+        let [clip_in_buf_l, clip_in_buf_r] = &mut state.clip_in_buf;
+        glue(
+            (clip_in_buf_l, clip_in_buf_r),
+            (&mut state.glue_reduction, &mut state.glue_hold_left),
+            if is_sidechain_hpf {
+                Some(&mut state.sidechain_filter)
+            } else {
+                None
+            },
+            (glue_threshold, glue_ratio),
+            (
+                one_pole_coef(glue_attack, state.sample_rate),
+                one_pole_coef(glue_release, state.sample_rate),
+                (glue_hold / 1000.0 * state.sample_rate) as u32,
+            ),
+        );
+    } else {
+        state.glue_reduction = 0.0;
+        state.glue_hold_left = 0;
+        state.sidechain_freq = 0.0;
+    }
+
+    // Transient emphasis, punchier hits going into the clipper
+    if is_transient {
+        let [clip_in_buf_l, clip_in_buf_r] = &mut state.clip_in_buf;
+        transient(
+            (clip_in_buf_l, clip_in_buf_r),
+            &mut state.transient_env,
+            transient_amount,
+            (
+                one_pole_coef(TRANSIENT_FAST_MS, state.sample_rate),
+                one_pole_coef(TRANSIENT_SLOW_MS, state.sample_rate),
+                one_pole_coef(TRANSIENT_RELEASE_MS, state.sample_rate),
+            ),
+        );
+    } else {
+        state.transient_env = [0.0; 2];
+    }
+
+    // Pre-emphasis, boost the highs going into the clipper
+    if is_emphasis {
+        set_emphasis(state, emphasis_db);
+        let [clip_in_buf_l, clip_in_buf_r] = &mut state.clip_in_buf;
+        shelve((clip_in_buf_l, clip_in_buf_r), &mut state.emphasis_pre);
+    } else {
+        state.emphasis_db = 0.0;
+    }
+
+    // Lose precision before the clamp when the order asks for it, the clipper then
+    // rounds off the stair steps instead of the crusher chopping up the clipped wave
+    let crush_target = crush_grid(crush_bits, quant_levels);
+    if is_crush_first {
+        let [clip_in_buf_l, clip_in_buf_r] = &mut state.clip_in_buf;
+        lose_precision(
+            (clip_in_buf_l, clip_in_buf_r),
+            (&mut state.bitify_amount, &mut state.crush_resolution),
+            state.sample_rate,
+            is_lose_precision,
+            (crush_target, is_mu_law, crush_stereo),
+        );
+    }
+
+    state.shaper.set_curve(curve);
+    if params.curve_changed.swap(false, Ordering::Relaxed) {
+        // the editor only holds the points for a moment, if it has them now try next block
+        match params.curve_points.try_lock() {
+            Ok(points) => state.shaper.set_custom_points(&points),
+            Err(_) => params.curve_changed.store(true, Ordering::Relaxed),
+        }
+    }
+
+    // Per sample glide for the threshold, shared by the clipper and the output ceiling
+    state
+        .threshold_smooth
+        .fill(&mut state.threshold_buf[..block_len], clamp_range);
+
+    let (clip_in_l, clip_in_r): (&[f32], &[f32]) = if is_clip_in_copy {
+        (
+            &state.clip_in_buf[0][..block_len],
+            &state.clip_in_buf[1][..block_len],
+        )
+    } else {
+        (in_buf_l, in_buf_r)
+    };
+
+    // Analog drift, each side clips at its own slightly mismatched threshold
+    let is_drift = analog_drift > 0.0;
+    if is_drift {
+        for (drift_buf, drift) in state.drift_threshold_buf.iter_mut().zip(state.drift) {
+            let scale = to_linear(DRIFT_MAX_DB * analog_drift * drift);
+            for (drift_sample, threshold) in
+                drift_buf.iter_mut().zip(&state.threshold_buf[..block_len])
+            {
+                *drift_sample = threshold * scale;
+            }
+        }
+    }
+    let thresholds: (&[f32], &[f32]) = if is_drift {
+        (
+            &state.drift_threshold_buf[0][..block_len],
+            &state.drift_threshold_buf[1][..block_len],
+        )
+    } else {
+        (
+            &state.threshold_buf[..block_len],
+            &state.threshold_buf[..block_len],
+        )
+    };
+
+    // Band solo, only while the split mode is on, the others drop out and so does the dry
+    let is_band_solo = clip_mode == ClipMode::Split && (solo_low || solo_high);
+    let band_gain = |is_solo: bool| if is_band_solo && !is_solo { 0.0 } else { 1.0 };
+    let split_gains = (band_gain(solo_low), band_gain(solo_high));
+
+    clip(
+        state.clip_mode,
+        (clip_in_l, clip_in_r),
+        (out_buf_l, out_buf_r),
+        (&mut state.split_clip, split_gains),
+        &state.shaper,
+        thresholds,
+    );
+
+    if state.clip_fade < 1.0 {
+        let [fade_buf_l, fade_buf_r] = &mut state.clip_fade_buf;
+        let (fade_buf_l, fade_buf_r) = (&mut fade_buf_l[..block_len], &mut fade_buf_r[..block_len]);
+        clip(
+            state.clip_mode_prev,
+            (clip_in_l, clip_in_r),
+            (fade_buf_l, fade_buf_r),
+            (&mut state.split_clip, split_gains),
+            &state.shaper,
+            thresholds,
+        );
+        state.clip_fade = crossfade(
+            (fade_buf_l, fade_buf_r),
+            (out_buf_l, out_buf_r),
+            state.clip_fade,
+            fade_step,
+        );
+    }
+
+    // Saturation floor, rounds off the approach to the threshold so the clip comes in gradually
+    if sat_floor_amount > 0.0 {
+        let (thresholds_l, thresholds_r) = thresholds;
+        for (out_buf_l_sample, threshold) in out_buf_l.iter_mut().zip(thresholds_l) {
+            *out_buf_l_sample = sat_floor(*out_buf_l_sample, *threshold, sat_floor_amount);
+        }
+        for (out_buf_r_sample, threshold) in out_buf_r.iter_mut().zip(thresholds_r) {
+            *out_buf_r_sample = sat_floor(*out_buf_r_sample, *threshold, sat_floor_amount);
+        }
+    }
+    // how hard the clipper was hit, drive, glue and the rest included, the lower side
+    // clips first when they drift apart
+    let (thresholds_l, thresholds_r) = thresholds;
+    let clip_threshold = match (thresholds_l.last(), thresholds_r.last()) {
+        (Some(threshold_l), Some(threshold_r)) => threshold_l.min(*threshold_r),
+        _ => clamp_range,
+    };
+    let clip_drive_db =
+        to_db(block_peak(clip_in_l).max(block_peak(clip_in_r))) - to_db(clip_threshold);
+    if is_metering {
+        params.meters.write_clip(
+            (clip_in_l, clip_in_r),
+            (out_buf_l, out_buf_r),
+            clip_threshold,
+        );
+    }
+
+    // De-emphasis, undoes the boost so only the clipped harmonics stay shifted
+    if is_emphasis {
+        shelve((out_buf_l, out_buf_r), &mut state.emphasis_post);
     }
 
-    // Lose precision
-    if lose_precision > 0.5 {
-        for out_buf_l_sample in &mut *out_buf_l {
-            let sample = (*out_buf_l_sample * 0x0f as f32) as i8;
-            *out_buf_l_sample = f32::from(sample) / 0x0f as f32;
+    // Second clip stage, stacked on the first
+    if is_clip2 {
+        if clip2_mode != state.clip2_mode {
+            if clip2_mode == ClipMode::Split {
+                for crossover in &mut state.clip2_split {
+                    crossover.reset();
+                }
+            }
+            state.clip2_mode = clip2_mode;
         }
+        let clip2_thresholds = &mut state.clip2_threshold_buf[..block_len];
+        state
+            .clip2_threshold_smooth
+            .fill(clip2_thresholds, clip2_threshold);
+        let [clip2_buf_l, clip2_buf_r] = &mut state.clip2_buf;
+        let (clip2_buf_l, clip2_buf_r) =
+            (&mut clip2_buf_l[..block_len], &mut clip2_buf_r[..block_len]);
+        clip2_buf_l.copy_from_slice(out_buf_l);
+        clip2_buf_r.copy_from_slice(out_buf_r);
+        clip(
+            clip2_mode,
+            (clip2_buf_l, clip2_buf_r),
+            (out_buf_l, out_buf_r),
+            (&mut state.clip2_split, (1.0, 1.0)),
+            &state.shaper,
+            (clip2_thresholds, clip2_thresholds),
+        );
+    }
 
-        for out_buf_r_sample in &mut *out_buf_r {
-            let sample = (*out_buf_r_sample * 0x0f as f32) as i8;
-            *out_buf_r_sample = f32::from(sample) / 0x0f as f32;
+    // Clamp mid and side separately
+    if is_ms_clip {
+        clip_mid_side(
+            (out_buf_l, out_buf_r),
+            (mid_threshold, side_threshold),
+            is_side_limit,
+        );
+    }
+
+    // Tape coloration on top of the clipped signal
+    if tape_amount > 0.0 {
+        tape((out_buf_l, out_buf_r), state, tape_amount);
+    }
+
+    // Wow and flutter, the tape's speed never quite holds still
+    wow_flutter((out_buf_l, out_buf_r), state, (wow, flutter));
+
+    // gain, or taking the drive back out again when linked
+    if gain_mode != GainMode::Drive {
+        for ((out_buf_l_sample, out_buf_r_sample), gain) in out_buf_l
+            .iter_mut()
+            .zip(out_buf_r.iter_mut())
+            .zip(&state.gain_buf)
+        {
+            let gain = if gain_mode == GainMode::Linked {
+                1.0 / gain
+            } else {
+                *gain
+            };
+            *out_buf_l_sample *= gain;
+            *out_buf_r_sample *= gain;
         }
     }
 
+    // Remove silence on the distorted signal
+    if silence_position == SilencePosition::PostClip {
+        remove_silence(
+            (out_buf_l, out_buf_r),
+            &mut state.silence_counter,
+            (is_legacy_silence, is_silence_linked),
+        );
+    }
+
+    // Lose precision, fading in and out when toggled
+    if !is_crush_first {
+        lose_precision(
+            (out_buf_l, out_buf_r),
+            (&mut state.bitify_amount, &mut state.crush_resolution),
+            state.sample_rate,
+            is_lose_precision,
+            (crush_target, is_mu_law, crush_stereo),
+        );
+    }
+
+    // Safety low-pass, tames the aliasing when the clipper is slammed
+    safety_filter(
+        (out_buf_l, out_buf_r),
+        state,
+        (is_safety_lpf, clip_drive_db > SAFETY_DRIVE_DB),
+    );
+
+    // Bass mono
+    if is_mono_maker {
+        mono_maker((out_buf_l, out_buf_r), state, mono_maker_freq);
+    }
+
+    // Trance gate, a reset from the editor starts the pattern over from here
+    if params.gate_reset.swap(false, Ordering::Relaxed) {
+        state.gate_anchor = transport.ppq_pos;
+    }
+    if is_gate {
+        trance_gate(
+            (out_buf_l, out_buf_r),
+            state,
+            (&gate_steps, gate_step_beats, gate_curve),
+            transport,
+        );
+    }
+
+    // Dry EQ match, only the tape changes the wet tone for good, the emphasis undoes itself
+    if is_dry_match && tape_amount > 0.0 {
+        dry_match((in_buf_l, in_buf_r), state);
+    } else {
+        state.dry_match_cutoff = 0.0;
+    }
+
+    // Level match, the dry signal follows the wet one's loudness
+    if is_level_match {
+        level_match((in_buf_l, in_buf_r), (out_buf_l, out_buf_r), state);
+    } else {
+        state.level_match_power = [0.0; 2];
+    }
+
     // Mix
-    mix((in_buf_l, in_buf_r), (out_buf_l, out_buf_r), mix_level);
+    // the bleed keeps a little dry signal in, on top of whatever the mix knob lets through,
+    // compare plays only the dry signal and glides over like the mix knob would
+    let mix_level = if is_compare {
+        0.0
+    } else if is_band_solo {
+        1.0
+    } else if is_dry_bleed {
+        mix_level * (1.0 - DRY_BLEED)
+    } else {
+        mix_level
+    };
+    if is_parallel && !is_band_solo {
+        parallel_mix(
+            (in_buf_l, in_buf_r),
+            (out_buf_l, out_buf_r),
+            state,
+            (mix_level, wet_hpf),
+        );
+    } else {
+        state.wet_freq = 0.0;
+        mix(
+            (in_buf_l, in_buf_r),
+            (out_buf_l, out_buf_r),
+            &mut state.mix_smooth,
+            mix_level,
+        );
+    }
+
+    // Fade back in after waking from power save, whatever was left in the state goes unheard
+    if state.wake_fade < 1.0 {
+        state.wake_fade = fade_in((out_buf_l, out_buf_r), state.wake_fade, fade_step);
+    }
+
+    // Freeze, loops the latest output while held
+    freeze((out_buf_l, out_buf_r), state, is_freeze, fade_step);
+
+    // Dither for the final bit depth
+    if is_output_dither {
+        dither((out_buf_l, out_buf_r), state);
+    }
+
+    // Clamp again at the very end so the threshold doubles as the output ceiling, nothing
+    // after this can push past it
+    if output_ceiling {
+        for (out_buf_l_sample, threshold) in out_buf_l.iter_mut().zip(&state.threshold_buf) {
+            *out_buf_l_sample = out_buf_l_sample.clamp(-threshold, *threshold);
+        }
+
+        for (out_buf_r_sample, threshold) in out_buf_r.iter_mut().zip(&state.threshold_buf) {
+            *out_buf_r_sample = out_buf_r_sample.clamp(-threshold, *threshold);
+        }
+    }
+
+    // Mono check from the editor, last so both sides come out identical
+    if is_check_mono {
+        for (out_buf_l_sample, out_buf_r_sample) in out_buf_l.iter_mut().zip(out_buf_r.iter_mut()) {
+            let mono = (*out_buf_l_sample + *out_buf_r_sample) * 0.5;
+            *out_buf_l_sample = mono;
+            *out_buf_r_sample = mono;
+        }
+    }
+
+    if !is_metering {
+        return;
+    }
+    params
+        .meters
+        .envelope
+        .set(state.envelope.process_block((out_buf_l, out_buf_r)));
+
+    // measured on what actually leaves the plugin, inter-sample peaks included,
+    // the upsampling only feeds the meter so turning it off leaves the audio as is.
+    // It runs here rather than in the editor since the meters only hand over one value
+    // per block, not the samples, and it costs 48 multiply-adds a sample per side while on
+    let [true_peak_l, true_peak_r] = &mut state.true_peak;
+    let true_peak = if is_true_peak_meter {
+        true_peak_l
+            .block_peak(out_buf_l)
+            .max(true_peak_r.block_peak(out_buf_r))
+    } else {
+        true_peak_l.reset();
+        true_peak_r.reset();
+        block_peak(out_buf_l).max(block_peak(out_buf_r))
+    };
+    params
+        .meters
+        .write_output((out_buf_l, out_buf_r), true_peak);
+}
+
+#[cfg(test)]
+mod tests {
+    use vst::plugin::PluginParameters;
+
+    use super::*;
+    use crate::analysis::block_rms;
+    use crate::param::PARAM_NUM;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+
+    fn sine(freq: f32, len: usize, amplitude: f32) -> Vec<f32> {
+        (0..len)
+            .map(|index| amplitude * (TAU * freq * index as f32 / SAMPLE_RATE).sin())
+            .collect()
+    }
+
+    // one block of `block` on both sides through the whole chain, left then right
+    fn run_block(params: &EffectParams, state: &mut DspState, block: &[f32]) -> Vec<f32> {
+        let transport = Transport {
+            ppq_pos: 0.0,
+            tempo: 120.0,
+            is_playing: true,
+        };
+        let (mut in_l, mut in_r) = (block.to_vec(), block.to_vec());
+        let (mut out_l, mut out_r) = (vec![0.0; block.len()], vec![0.0; block.len()]);
+        process(
+            &mut in_l, &mut in_r, &mut out_l, &mut out_r, params, state, &transport,
+        );
+        out_l.extend(out_r);
+        out_l
+    }
+
+    // a few blocks of `input` from a fresh state
+    fn run(params: &EffectParams, input: &[f32]) -> Vec<f32> {
+        let mut state = DspState::default();
+        state.set_sample_rate(SAMPLE_RATE);
+        input
+            .chunks(512)
+            .flat_map(|block| run_block(params, &mut state, block))
+            .collect()
+    }
+
+    // defaults, then every host param at its bottom and at its top, with how loud
+    // a full scale input may come out, +48 dB of gain is the most any setting asks for
+    fn param_sets() -> Vec<(&'static str, EffectParams, f32)> {
+        let extreme = |val: f32| {
+            let params = EffectParams::default();
+            for index in 0..PARAM_NUM {
+                params.set_parameter(index, val);
+            }
+            params
+        };
+        vec![
+            ("default", EffectParams::default(), 1.0),
+            ("all min", extreme(0.0), to_linear(48.0)),
+            ("all max", extreme(1.0), to_linear(48.0)),
+        ]
+    }
+
+    fn assert_sane(name: &str, output: &[f32], bound: f32) {
+        for sample in output {
+            assert!(sample.is_finite(), "{}: {}", name, sample);
+            assert!(sample.abs() <= bound, "{}: {}", name, sample);
+        }
+    }
+
+    #[test]
+    fn impulse_stays_finite_and_bounded() {
+        let mut impulse = vec![0.0; 8192];
+        impulse[100] = 1.0;
+        for (name, params, bound) in param_sets() {
+            assert_sane(name, &run(&params, &impulse), bound);
+        }
+    }
+
+    #[test]
+    fn full_scale_sine_stays_finite_and_bounded() {
+        let sine = sine(997.0, 8192, 1.0);
+        for (name, params, bound) in param_sets() {
+            assert_sane(name, &run(&params, &sine), bound);
+        }
+    }
+
+    #[test]
+    fn poisoned_filter_state_starts_over() {
+        let params = EffectParams::default();
+        // tape, so its filters are in the path
+        params.set_parameter(34, 1.0);
+        let mut state = DspState::default();
+        state.set_sample_rate(SAMPLE_RATE);
+        let block = sine(997.0, 512, 0.5);
+        run_block(&params, &mut state, &block);
+        state.tape_filter[0].process(f32::NAN);
+        assert_sane("poisoned", &run_block(&params, &mut state, &block), 1.0);
+        assert_eq!(params.meters.state_resets.load(Ordering::Relaxed), 1);
+        assert_sane("after", &run_block(&params, &mut state, &block), 1.0);
+        assert_eq!(params.meters.state_resets.load(Ordering::Relaxed), 1);
+    }
+
+    // silence through the given clip mode with the threshold at the bottom of its range
+    fn silence_at_zero_threshold(mode: ClipMode) -> Vec<f32> {
+        let params = EffectParams::default();
+        params.set_parameter(0, 0.0);
+        params.clip_mode.set(mode as usize as f32);
+        run(&params, &[0.0; 2048])
+    }
+
+    #[test]
+    fn split_clip_at_zero_threshold_stays_finite() {
+        assert_eq!(soft_clip(0.0, 0.0), 0.0);
+        assert_sane("split", &silence_at_zero_threshold(ClipMode::Split), 0.0);
+    }
+
+    #[test]
+    fn curve_clip_at_zero_threshold_stays_finite() {
+        assert_sane("curve", &silence_at_zero_threshold(ClipMode::Curve), 0.0);
+    }
+
+    #[test]
+    fn inverted_dry_nulls_against_the_original() {
+        let input = sine(997.0, 8192, 0.5);
+        let dry = |is_inverted: bool| {
+            let params = EffectParams::default();
+            params.set_parameter(2, 0.0);
+            if is_inverted {
+                params.set_parameter(4, 1.0);
+                params.set_parameter(5, 1.0);
+            }
+            // past the mix's glide down from its default
+            run(&params, &input).split_off(8192)
+        };
+        let (original, inverted) = (dry(false), dry(true));
+        assert!(block_peak(&original) > 0.4);
+        for (original, inverted) in original.iter().zip(&inverted) {
+            assert!((original + inverted).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn output_ceiling_holds_after_gain_and_dither() {
+        // the peak sits right on the threshold however hard the gain pushes past it,
+        // from about 6 dB of gain on the sine reaches it
+        for gain in [0.25, 0.5, 0.75, 1.0] {
+            let params = EffectParams::default();
+            // ceiling, gain, dither and tape
+            for (index, val) in [(6, 1.0), (3, gain), (10, 1.0), (34, 1.0)] {
+                params.set_parameter(index, val);
+            }
+            let (threshold, _, _) = params.amount_shaped();
+            // past the threshold's glide from where it starts
+            let output = run(&params, &sine(997.0, 16384, 1.0)).split_off(16384);
+            assert_sane("ceiling", &output, threshold + 1e-6);
+            let peak = block_peak(&output);
+            assert!((peak - threshold).abs() < 1e-3, "gain {}: {}", gain, peak);
+        }
+    }
+
+    #[test]
+    fn silence_gate_follows_the_processed_signal() {
+        // under the gate on the way in, well over it once the gain is on
+        let input = sine(997.0, 8192, 0.01);
+        let gain = to_linear(20.0);
+        let gated = |position: SilencePosition| {
+            let params = EffectParams::default();
+            params.set_parameter(1, 0.0);
+            params.gain.set(gain);
+            params.silence_position.set(position as usize as f32);
+            let mut state = DspState::default();
+            state.set_sample_rate(SAMPLE_RATE);
+            let mut output = Vec::new();
+            for block in input.chunks(512) {
+                let (mut in_l, mut in_r) = (block.to_vec(), block.to_vec());
+                // loud leftovers from the host, the gate must not go by these
+                let (mut out_l, mut out_r) = (vec![1.0; block.len()], vec![1.0; block.len()]);
+                process(
+                    &mut in_l,
+                    &mut in_r,
+                    &mut out_l,
+                    &mut out_r,
+                    &params,
+                    &mut state,
+                    &Transport::default(),
+                );
+                output.extend(out_l);
+            }
+            // past the gain's glide up from unity
+            output.split_off(4096)
+        };
+        let energy = |buf: &[f32]| buf.iter().map(|sample| sample * sample).sum::<f32>();
+        let wet: Vec<f32> = input[4096..].iter().map(|sample| sample * gain).collect();
+
+        let post_clip = energy(&gated(SilencePosition::PostClip));
+        assert!((post_clip / energy(&wet) - 1.0).abs() < 0.01);
+        assert_eq!(energy(&gated(SilencePosition::PreClip)), 0.0);
+    }
+
+    #[test]
+    fn legacy_silence_gate_matches_the_original() {
+        // the original gate on one channel, its count started over every block
+        let original = |block: &[f32]| -> Vec<f32> {
+            let mut silence_counter: i32 = 0;
+            block
+                .iter()
+                .map(|&sample| {
+                    if sample < SILENT_THRESHOLD_DB {
+                        silence_counter += 1;
+                        if silence_counter > SILENT_THRESHOLD_COUNT {
+                            return 0.0;
+                        }
+                    }
+                    sample
+                })
+                .collect()
+        };
+        let (loud, quiet) = (sine(997.0, 1024, 0.5), sine(61.0, 1024, 0.01));
+        let mut silence_counter = [0; 2];
+        for (loud, quiet) in loud.chunks(512).zip(quiet.chunks(512)) {
+            let (mut buf_l, mut buf_r) = (loud.to_vec(), quiet.to_vec());
+            remove_silence(
+                (&mut buf_l, &mut buf_r),
+                &mut silence_counter,
+                (true, false),
+            );
+            assert_eq!(buf_l, original(loud));
+            assert_eq!(buf_r, original(quiet));
+            // the quirk, the loud wave loses its negative half
+            assert!(buf_l[128..].iter().all(|sample| *sample >= 0.0));
+
+            // while the default gate leaves a loud wave alone
+            let mut buf_l = loud.to_vec();
+            remove_silence(
+                (&mut buf_l, &mut quiet.to_vec()),
+                &mut silence_counter,
+                (false, false),
+            );
+            assert_eq!(buf_l, loud);
+        }
+    }
+
+    #[test]
+    fn same_seed_renders_bit_for_bit() {
+        let render = |seed: f32| {
+            let params = EffectParams::default();
+            params.seed.set(seed);
+            // noise in, then every stage that draws from the seed
+            params
+                .test_signal
+                .set(TestSignalMode::Noise as usize as f32);
+            params.set_parameter(10, 1.0);
+            params.set_parameter(71, 1.0);
+            run(&params, &[0.0; 4096])
+        };
+        assert_eq!(render(1234.0), render(1234.0));
+        assert_ne!(render(1234.0), render(4321.0));
+    }
+
+    #[test]
+    fn power_save_fades_back_in_and_keeps_the_gate_anchor() {
+        // a second of silence, then signal, once with power save and once without
+        let wake = |is_power_save: bool| {
+            let params = EffectParams::default();
+            params.set_parameter(1, 0.0);
+            params.set_parameter(84, if is_power_save { 1.0 } else { 0.0 });
+            let mut state = DspState::default();
+            state.set_sample_rate(SAMPLE_RATE);
+            state.gate_anchor = 2.0;
+            for _ in 0..100 {
+                run_block(&params, &mut state, &[0.0; 512]);
+            }
+            assert_eq!(state.is_sleeping, is_power_save);
+            let output = run_block(&params, &mut state, &sine(997.0, 512, 0.2));
+            assert_eq!(state.gate_anchor, 2.0);
+            output
+        };
+        let (slept, awake) = (wake(true), wake(false));
+        let fade_step = 1000.0 / (MODE_FADE_MS * SAMPLE_RATE);
+        for (index, (slept, awake)) in slept.iter().zip(&awake).take(512).enumerate() {
+            let fade = ((index + 1) as f32 * fade_step).min(1.0);
+            assert!((slept - awake * fade).abs() < 1e-6, "sample {}", index);
+        }
+    }
+
+    #[test]
+    fn toggling_8_bitify_mid_tone_does_not_click() {
+        // quiet enough that 3 bits truncate it all away, a hard switch would jump
+        // straight from the crest of the wave to silence
+        let input = sine(100.0, 4410, 0.2);
+        let params = EffectParams::default();
+        params.crush.set(13.0 / 15.0);
+        let mut state = DspState::default();
+        state.set_sample_rate(SAMPLE_RATE);
+        let mut output = Vec::new();
+        // switched at a crest, on, then off again three cycles later
+        for (block, is_on) in [(0..110, false), (110..1433, true), (1433..4410, false)] {
+            params.set_parameter(1, if is_on { 1.0 } else { 0.0 });
+            let len = block.len();
+            output.extend_from_slice(&run_block(&params, &mut state, &input[block])[..len]);
+        }
+        // the steepest the clean tone gets is under 0.003 a sample
+        for pair in output.windows(2) {
+            assert!((pair[1] - pair[0]).abs() < 0.005, "{:?}", pair);
+        }
+        assert!(block_peak(&output[1000..1400]) < 1e-6);
+    }
+
+    #[test]
+    fn true_peak_metering_leaves_the_audio_alone() {
+        // a quarter of the sample rate, shifted so every sample misses the crest
+        let input: Vec<f32> = (0..4096)
+            .map(|index| 0.2 * (TAU * (index as f32 / 4.0 + 0.125)).sin())
+            .collect();
+        let render = |is_true_peak: bool| {
+            let params = EffectParams::default();
+            params.set_parameter(1, 0.0);
+            params.set_parameter(65, if is_true_peak { 1.0 } else { 0.0 });
+            let output = run(&params, &input);
+            (output, params.meters.output_true_peak.get())
+        };
+        let (with_true_peak, true_peak) = render(true);
+        let (without, sample_peak) = render(false);
+        assert_eq!(with_true_peak, without);
+        assert!((sample_peak - 0.2 * FRAC_1_SQRT_2).abs() < 1e-3);
+        assert!(true_peak > sample_peak * 1.2);
+    }
+
+    #[test]
+    fn bass_mono_centers_the_lows_and_keeps_the_highs_wide() {
+        // how much side is left of a tone that was all side going in
+        let width = |freq: f32| {
+            let mut state = DspState::default();
+            state.set_sample_rate(SAMPLE_RATE);
+            let mut buf_l = sine(freq, 8192, 0.5);
+            let mut buf_r: Vec<f32> = buf_l.iter().map(|sample| -sample).collect();
+            mono_maker((&mut buf_l, &mut buf_r), &mut state, 120.0);
+            // past the crossover settling in
+            let side: Vec<f32> = buf_l
+                .iter()
+                .zip(&buf_r)
+                .skip(4096)
+                .map(|(sample_l, sample_r)| (sample_l - sample_r) * 0.5)
+                .collect();
+            block_peak(&side)
+        };
+        assert!(width(20.0) < 1e-3);
+        assert!((width(5000.0) - 0.5).abs() < 1e-2);
+    }
+
+    #[test]
+    fn glue_reduces_by_the_ratio_above_the_threshold() {
+        // where a steady level settles, in dB, at -18 dB and 4:1
+        let settled = |level_db: f32| {
+            let mut buf_l = vec![to_linear(level_db); 44100];
+            let mut buf_r = buf_l.clone();
+            glue(
+                (&mut buf_l, &mut buf_r),
+                (&mut 0.0, &mut 0),
+                None,
+                (to_linear(-18.0), 4.0),
+                (
+                    one_pole_coef(30.0, SAMPLE_RATE),
+                    one_pole_coef(200.0, SAMPLE_RATE),
+                    0,
+                ),
+            );
+            to_db(buf_l[buf_l.len() - 1])
+        };
+        // 12 dB over comes out 3 dB over, anything under is left alone
+        assert!((settled(-6.0) + 15.0).abs() < 0.01);
+        assert!((settled(-24.0) + 24.0).abs() < 1e-4);
+        // and a 12 dB swing above the threshold shrinks to a quarter of it
+        assert!((settled(0.0) - settled(-12.0) - 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn gate_opens_and_closes_on_the_step_boundaries() {
+        // every other 16th open, at 120 bpm a 16th is 5512.5 samples
+        let steps: [bool; GATE_STEPS] = std::array::from_fn(|step| step % 2 == 0);
+        let step_samples = 0.25 * 60.0 / 120.0 * SAMPLE_RATE as f64;
+        let mut state = DspState::default();
+        state.set_sample_rate(SAMPLE_RATE);
+        let transport = Transport {
+            ppq_pos: 0.0,
+            tempo: 120.0,
+            is_playing: true,
+        };
+        let (mut buf_l, mut buf_r) = (vec![1.0; 22050], vec![1.0; 22050]);
+        trance_gate(
+            (&mut buf_l, &mut buf_r),
+            &mut state,
+            (&steps, 0.25, GateCurve::Linear),
+            &transport,
+        );
+        // a linear ramp is half way through its 2 ms half a ramp after the edge
+        let ramp_samples = GATE_SMOOTH_MS / 1000.0 * SAMPLE_RATE;
+        for step in 1..4 {
+            let edge = (step as f64 * step_samples).ceil() as usize;
+            let is_opening = step % 2 == 0;
+            let (before, after) = (buf_l[edge - 1], buf_l[edge + ramp_samples as usize + 1]);
+            if is_opening {
+                assert_eq!((before, after), (0.0, 1.0), "step {step}");
+            } else {
+                assert_eq!((before, after), (1.0, 0.0), "step {step}");
+            }
+            let half_way = edge + (ramp_samples / 2.0) as usize;
+            assert!((buf_l[half_way] - 0.5).abs() < 0.02, "step {step}");
+        }
+    }
+
+    #[test]
+    fn transport_jump_lands_the_gate_on_the_new_step() {
+        // only the first 16th of every bar open
+        let mut steps = [false; GATE_STEPS];
+        steps[0] = true;
+        let mut state = DspState::default();
+        state.set_sample_rate(SAMPLE_RATE);
+        // the gate's gain at the end of a block played from `ppq_pos`
+        let mut gain_at = |ppq_pos: f64| {
+            let transport = Transport {
+                ppq_pos,
+                tempo: 120.0,
+                is_playing: true,
+            };
+            // a block is short of a 16th at this tempo, so it stays on one step
+            let (mut buf_l, mut buf_r) = (vec![1.0; 1024], vec![1.0; 1024]);
+            trance_gate(
+                (&mut buf_l, &mut buf_r),
+                &mut state,
+                (&steps, 0.25, GateCurve::Exponential),
+                &transport,
+            );
+            buf_l[1023]
+        };
+        assert!(gain_at(0.0) > 0.99);
+        // a jump into the closed 6th step closes it right there
+        assert!(gain_at(1.25) < 0.01);
+        // looping back round to a bar line opens it again
+        assert!(gain_at(4.0) > 0.99);
+        // hosts that only report samples land on the same steps, 2.5 s in is beat 5
+        let ppq_pos =
+            Transport::ppq_from_samples(2.5 * SAMPLE_RATE as f64, 120.0, SAMPLE_RATE as f64);
+        assert_eq!(ppq_pos, 5.0);
+        assert!(gain_at(ppq_pos) < 0.01);
+    }
+
+    #[test]
+    fn dither_noise_is_quiet_and_pushed_up_high() {
+        let input = sine(997.0, 16384, 0.1);
+        let mut state = DspState::default();
+        let (mut buf_l, mut buf_r) = (input.clone(), input.clone());
+        dither((&mut buf_l, &mut buf_r), &mut state);
+        let noise: Vec<f32> = buf_l
+            .iter()
+            .zip(&input)
+            .map(|(out, ins)| out - ins)
+            .collect();
+        let lsb = 1.0 / (1 << (DITHER_BITS - 1)) as f32;
+        let rms =
+            (noise.iter().map(|sample| sample * sample).sum::<f32>() / noise.len() as f32).sqrt();
+        assert!(rms > 0.5 * lsb && rms < 4.0 * lsb, "{} lsb", rms / lsb);
+        // white noise would split evenly between the sum and the difference of
+        // neighbouring samples, the 2nd order shaping puts 5 times as much in the difference
+        let band = |sign: f32| {
+            noise
+                .windows(2)
+                .map(|pair| (pair[1] + sign * pair[0]).powi(2))
+                .sum::<f32>()
+        };
+        assert!(band(-1.0) > 3.0 * band(1.0));
+    }
+
+    #[test]
+    fn emphasis_undoes_itself_when_nothing_clips() {
+        // the lows, the corner and the top of the shelves, all well under the threshold
+        let input: Vec<f32> = sine(100.0, 8192, 0.04)
+            .iter()
+            .zip(sine(3000.0, 8192, 0.04))
+            .zip(sine(12000.0, 8192, 0.02))
+            .map(|((low, mid), high)| low + mid + high)
+            .collect();
+        let plain = run(&EffectParams::default(), &input);
+        let params = EffectParams::default();
+        params.emphasis.set(0.0);
+        assert_eq!(run(&params, &input), plain);
+        params.emphasis.set(1.0);
+        for (emphasised, plain) in run(&params, &input).iter().zip(&plain) {
+            assert!((emphasised - plain).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn level_match_makes_the_dry_as_loud_as_the_wet() {
+        // quiet in, driven hard into the clipper, so the wet comes out far louder
+        let input = sine(440.0, 4 * SAMPLE_RATE as usize, 0.05);
+        let loudness_db = |is_compare: bool, is_level_match: bool| {
+            let params = EffectParams::default();
+            params.set_parameter(3, 1.0);
+            params.set_parameter(86, if is_compare { 1.0 } else { 0.0 });
+            params.set_parameter(87, if is_level_match { 1.0 } else { 0.0 });
+            let mut output = run(&params, &input);
+            // the last second, once the loudness has settled
+            to_db(block_rms(&output.split_off(output.len() * 3 / 4)))
+        };
+        let wet_db = loudness_db(false, true);
+        assert!(wet_db - loudness_db(true, false) > 6.0);
+        assert!((loudness_db(true, true) - wet_db).abs() < 0.5);
+        // the match only touches the dry, fully wet it changes nothing
+        assert!((loudness_db(false, false) - wet_db).abs() < 1e-3);
+    }
+
+    #[test]
+    fn blocks_up_to_the_announced_size_do_not_reallocate() {
+        let block_ptrs = |state: &DspState| -> Vec<*const f32> {
+            state
+                .clip_in_buf
+                .iter()
+                .chain(&state.drift_threshold_buf)
+                .chain(&state.clip_fade_buf)
+                .chain(&state.clip2_buf)
+                .chain([
+                    &state.threshold_buf,
+                    &state.clip2_threshold_buf,
+                    &state.gain_buf,
+                ])
+                .map(|buf| buf.as_ptr())
+                .collect()
+        };
+        // every stage with a block buffer of its own, right after a clip mode switch
+        let params = EffectParams::default();
+        for param in [
+            &params.analog_drift,
+            &params.clip2,
+            &params.glue,
+            &params.clip_mode,
+        ] {
+            param.set(1.0);
+        }
+        let mut state = DspState::default();
+        state.set_sample_rate(SAMPLE_RATE);
+        state.set_block_size(512);
+        let ptrs = block_ptrs(&state);
+        for len in [512, 100, 512] {
+            run_block(&params, &mut state, &sine(997.0, len, 1.0));
+            assert_eq!(block_ptrs(&state), ptrs);
+        }
+        // past it, they grow instead of panicking
+        run_block(&params, &mut state, &sine(997.0, 1024, 1.0));
+        assert_eq!(state.gain_buf.len(), 1024);
+    }
+
+    #[test]
+    fn each_routing_mode_maps_its_channels() {
+        let (left, right) = (vec![0.25; 64], vec![-0.5; 64]);
+        for (mode, (expected_l, expected_r)) in [
+            (RoutingMode::Stereo, (&left, &right)),
+            (RoutingMode::Swap, (&right, &left)),
+            (RoutingMode::LeftOnly, (&left, &left)),
+            (RoutingMode::RightOnly, (&right, &right)),
+        ] {
+            let (mut buf_l, mut buf_r) = (left.clone(), right.clone());
+            route((&mut buf_l, &mut buf_r), mode);
+            assert_eq!((&buf_l, &buf_r), (expected_l, expected_r));
+        }
+    }
+
+    #[test]
+    fn shave_is_bit_transparent_below_the_threshold() {
+        let threshold = 0.5;
+        let input = sine(997.0, 1024, 1.0);
+        let mut shaved = input.clone();
+        for sample in &mut shaved {
+            *sample = shave(*sample, threshold);
+        }
+        for (shaved, input) in shaved.iter().zip(&input) {
+            if input.abs() <= threshold {
+                assert_eq!(shaved, input);
+            } else {
+                // the peaks round off within the knee, still rising and keeping their sign
+                assert!(shaved.abs() >= threshold && shaved.abs() <= input.abs());
+                assert!(shaved.abs() < threshold * (1.0 + SHAVE_KNEE));
+                assert_eq!(shaved.signum(), input.signum());
+            }
+        }
+        assert!(block_peak(&shaved) < 0.7);
+    }
+
+    #[test]
+    fn side_clip_leaves_a_mono_input_alone() {
+        let input = sine(997.0, 1024, 0.8);
+        // the side clamped right down, the mid left open
+        let (mut buf_l, mut buf_r) = (input.clone(), input.clone());
+        clip_mid_side((&mut buf_l, &mut buf_r), (1.0, 0.01), false);
+        assert_eq!(buf_l, input);
+        assert_eq!(buf_r, input);
+        // while a wide one is pulled in
+        let mut buf_r: Vec<f32> = input.iter().map(|sample| -sample).collect();
+        let mut buf_l = input.clone();
+        clip_mid_side((&mut buf_l, &mut buf_r), (1.0, 0.01), false);
+        assert!(block_peak(&buf_l) <= 0.01 && block_peak(&buf_r) <= 0.01);
+    }
+
+    #[test]
+    fn side_limit_holds_a_wide_overload_and_leaves_the_center_alone() {
+        let (mid, side) = (sine(220.0, 1024, 0.3), sine(997.0, 1024, 2.0));
+        let mut buf_l: Vec<f32> = mid
+            .iter()
+            .zip(&side)
+            .map(|(mid, side)| mid + side)
+            .collect();
+        let mut buf_r: Vec<f32> = mid
+            .iter()
+            .zip(&side)
+            .map(|(mid, side)| mid - side)
+            .collect();
+        clip_mid_side((&mut buf_l, &mut buf_r), (1.0, 0.25), true);
+        for ((out_l, out_r), (mid, side)) in buf_l.iter().zip(&buf_r).zip(mid.iter().zip(&side)) {
+            assert!(((out_l + out_r) * 0.5 - mid).abs() < 1e-6);
+            let out_side = (out_l - out_r) * 0.5;
+            assert!(out_side.abs() <= 0.25);
+            // bent towards the threshold, never folded over
+            assert_eq!(out_side.signum(), side.signum());
+        }
+        assert!(block_peak(&buf_l) <= 0.3 + 0.25 + 1e-6);
+        // a side well below the threshold only gets bent a little
+        let quiet = sine(997.0, 1024, 0.025);
+        let mut buf_r: Vec<f32> = quiet.iter().map(|sample| -sample).collect();
+        let mut buf_l = quiet.clone();
+        clip_mid_side((&mut buf_l, &mut buf_r), (1.0, 0.25), true);
+        for (out_l, quiet) in buf_l.iter().zip(&quiet) {
+            assert!((out_l - quiet).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn sat_floor_keeps_unity_slope_and_meets_the_threshold() {
+        let threshold = 0.5;
+        // small signals pass as they are
+        assert_eq!(sat_floor(0.1, threshold, 1.0), 0.1);
+        assert_eq!(sat_floor(-0.1, threshold, 1.0), -0.1);
+        // the knee lands right on the threshold and overshoots pass through
+        assert!((sat_floor(threshold, threshold, 1.0) - threshold).abs() < 1e-6);
+        assert_eq!(sat_floor(0.7, threshold, 1.0), 0.7);
+        // rounds over into the threshold without ever turning back or going past it
+        let mut last = 0.0;
+        for index in 1..=1000 {
+            let sample = threshold * index as f32 / 1000.0;
+            let bent = sat_floor(sample, threshold, 1.0);
+            assert!(bent >= last && bent <= threshold + 1e-6);
+            last = bent;
+        }
+    }
+
+    // runs a sine through the crusher alone, past its fade in
+    fn crushed_sine(bits: f32) -> Vec<f32> {
+        let mut buf_l = sine(440.0, 4096, 0.8);
+        let mut buf_r = buf_l.clone();
+        let (mut amount, mut resolution) = (0.0, 0.0);
+        lose_precision(
+            (&mut buf_l, &mut buf_r),
+            (&mut amount, &mut resolution),
+            SAMPLE_RATE,
+            true,
+            (crush_grid(bits, 0.0), false, CrushStereo::LeftRight),
+        );
+        buf_l.split_off(1024)
+    }
+
+    #[test]
+    fn default_crush_matches_the_original_bit_for_bit() {
+        let bits = crush_to_bits(EffectParams::default().crush.get());
+        let original: Vec<f32> = sine(440.0, 4096, 0.8)
+            .split_off(1024)
+            .iter()
+            .map(|sample| f32::from((sample * 0x0f as f32) as i8) / 0x0f as f32)
+            .collect();
+        assert_eq!(crushed_sine(bits), original);
+    }
+
+    #[test]
+    fn low_bit_depths_are_not_silent() {
+        for bits in [1.0, 2.0] {
+            let crushed = crushed_sine(bits);
+            assert!(block_peak(&crushed) > 0.2, "{} bits went silent", bits);
+        }
+        // 1 bit is a square at half scale
+        assert!(crushed_sine(1.0)
+            .iter()
+            .all(|sample| (sample.abs() - 0.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn quant_levels_gives_exactly_that_many_levels() {
+        // full scale, digital silence is skipped as it passes as is
+        let ramp: Vec<f32> = (0..4000)
+            .map(|index| (index as f32 + 0.5) / 2000.0 - 1.0)
+            .collect();
+        for levels in [2, 3, 4, 5, 16, 255] {
+            let (resolution, quantizer) = crush_grid(16.0, levels as f32);
+            let mut seen: Vec<f32> = ramp
+                .iter()
+                .map(|sample| quantize(*sample, resolution, quantizer))
+                .collect();
+            seen.sort_by(f32::total_cmp);
+            seen.dedup_by(|a, b| (*a - *b).abs() < 1e-4);
+            assert_eq!(seen.len(), levels, "{} levels", levels);
+            assert!((seen[0] + 1.0).abs() < 1e-4 && (seen[levels - 1] - 1.0).abs() < 1e-4);
+        }
+    }
 }
@@ -0,0 +1,241 @@
+use std::f32::consts::FRAC_PI_2;
+
+use crate::param::{Curve, CURVE_NAMES, CURVE_POINTS_MAX, DEFAULT_CURVE_POINTS};
+
+/*
+ * Lookup table waveshaper
+ * Curves are built once, shaping a sample is a table read and a lerp
+ */
+
+const TABLE_SIZE: usize = 4096;
+// input range the tables cover, relative to the threshold
+const TABLE_RANGE: f32 = 4.0;
+// input the custom curve's points span, relative to the threshold, it stays flat past that
+pub const CUSTOM_CURVE_RANGE: f32 = 2.0;
+
+pub struct Waveshaper {
+    tables: Vec<Vec<f32>>,
+    curve: Curve,
+}
+
+impl Default for Waveshaper {
+    fn default() -> Waveshaper {
+        let tables = (0..CURVE_NAMES.len())
+            .map(|index| {
+                let curve = Curve::from_index(index as f32);
+                (0..TABLE_SIZE)
+                    .map(|point| shape(curve, table_input(point)))
+                    .collect()
+            })
+            .collect();
+        Waveshaper {
+            tables,
+            curve: Curve::Soft,
+        }
+    }
+}
+
+impl Waveshaper {
+    pub fn set_curve(&mut self, curve: Curve) {
+        self.curve = curve;
+    }
+
+    // refills the custom curve's table in place, no allocation
+    pub fn set_custom_points(&mut self, points: &[(f32, f32)]) {
+        let spline = MonotoneCubic::new(points);
+        for (point, value) in self.tables[Curve::Custom as usize].iter_mut().enumerate() {
+            *value = spline.custom_shape(table_input(point));
+        }
+    }
+
+    // shapes the sample so the curve's full scale lands on the threshold
+    pub fn process(&self, sample: f32, threshold: f32) -> f32 {
+        // a threshold pulled all the way down would look the table up with NaN
+        if threshold <= 0.0 {
+            return 0.0;
+        }
+        let table = &self.tables[self.curve as usize];
+        let position = ((sample / threshold / TABLE_RANGE + 1.0) * 0.5 * (TABLE_SIZE - 1) as f32)
+            .clamp(0.0, (TABLE_SIZE - 1) as f32);
+        let index = (position as usize).min(TABLE_SIZE - 2);
+        let frac = position - index as f32;
+        let shaped = table[index] + (table[index + 1] - table[index]) * frac;
+        shaped * threshold
+    }
+}
+
+// input the table holds at a point, relative to the threshold
+fn table_input(point: usize) -> f32 {
+    (point as f32 / (TABLE_SIZE - 1) as f32 * 2.0 - 1.0) * TABLE_RANGE
+}
+
+// the analytic curves, input and output relative to the threshold
+fn shape(curve: Curve, x: f32) -> f32 {
+    match curve {
+        Curve::Soft => x.tanh(),
+        Curve::Hard => x.clamp(-1.0, 1.0),
+        // cubic, smooth at the knee
+        Curve::Sigmoid => {
+            if x.abs() < 1.0 {
+                1.5 * x - 0.5 * x * x * x
+            } else {
+                x.signum()
+            }
+        }
+        // folds back down past the threshold instead of flattening
+        Curve::SineFold => (x * FRAC_PI_2).sin(),
+        // until the editor draws its own
+        Curve::Custom => MonotoneCubic::new(&DEFAULT_CURVE_POINTS).custom_shape(x),
+    }
+}
+
+/*
+ * Monotone cubic through a few points (Fritsch-Carlson)
+ * Slopes are limited so the curve never overshoots, points that never fall give a curve that
+ * never falls. Expects sanitized points, see param::sanitize_curve_points
+ */
+
+pub struct MonotoneCubic {
+    points: [(f32, f32); CURVE_POINTS_MAX],
+    slopes: [f32; CURVE_POINTS_MAX],
+    len: usize,
+}
+
+impl MonotoneCubic {
+    pub fn new(points: &[(f32, f32)]) -> MonotoneCubic {
+        let len = points.len().min(CURVE_POINTS_MAX);
+        let mut spline = MonotoneCubic {
+            points: [(0.0, 0.0); CURVE_POINTS_MAX],
+            slopes: [0.0; CURVE_POINTS_MAX],
+            len,
+        };
+        spline.points[..len].copy_from_slice(&points[..len]);
+        if len < 2 {
+            return spline;
+        }
+
+        let mut secants = [0.0; CURVE_POINTS_MAX];
+        for (secant, pair) in secants.iter_mut().zip(spline.points[..len].windows(2)) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            *secant = (y1 - y0) / (x1 - x0);
+        }
+        spline.slopes[0] = secants[0];
+        spline.slopes[len - 1] = secants[len - 2];
+        for point in 1..len - 1 {
+            let (before, after) = (secants[point - 1], secants[point]);
+            spline.slopes[point] = if before * after <= 0.0 {
+                0.0
+            } else {
+                (before + after) * 0.5
+            };
+        }
+        // flat segments stay flat, steep ones get their end slopes pulled in
+        for (segment, &secant) in secants[..len - 1].iter().enumerate() {
+            if secant == 0.0 {
+                spline.slopes[segment] = 0.0;
+                spline.slopes[segment + 1] = 0.0;
+                continue;
+            }
+            let a = spline.slopes[segment] / secant;
+            let b = spline.slopes[segment + 1] / secant;
+            let length = (a * a + b * b).sqrt();
+            if length > 3.0 {
+                spline.slopes[segment] = 3.0 / length * a * secant;
+                spline.slopes[segment + 1] = 3.0 / length * b * secant;
+            }
+        }
+        spline
+    }
+
+    // held flat outside the first and last point
+    pub fn eval(&self, x: f32) -> f32 {
+        let points = &self.points[..self.len];
+        match points {
+            [] => return 0.0,
+            [(_, y)] => return *y,
+            _ => (),
+        }
+        if x <= points[0].0 {
+            return points[0].1;
+        }
+        if x >= points[self.len - 1].0 {
+            return points[self.len - 1].1;
+        }
+        let segment = points[1..].iter().position(|&(px, _)| x < px).unwrap_or(0);
+        let ((x0, y0), (x1, y1)) = (points[segment], points[segment + 1]);
+        let width = x1 - x0;
+        let t = (x - x0) / width;
+        let (t2, t3) = (t * t, t * t * t);
+        (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * width * self.slopes[segment]
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * width * self.slopes[segment + 1]
+    }
+
+    // the points cover the positive half, the negative one mirrors it
+    pub fn custom_shape(&self, x: f32) -> f32 {
+        x.signum() * self.eval(x.abs() / CUSTOM_CURVE_RANGE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::param::sanitize_curve_points;
+
+    #[test]
+    fn table_matches_the_analytic_curves() {
+        let mut shaper = Waveshaper::default();
+        for (index, name) in CURVE_NAMES.iter().enumerate() {
+            let curve = Curve::from_index(index as f32);
+            shaper.set_curve(curve);
+            // right on the table points and halfway between them, at two thresholds
+            for step in 0..=2000 {
+                let x = (step as f32 / 1000.0 - 1.0) * TABLE_RANGE;
+                for threshold in [1.0, 0.25] {
+                    let shaped = shaper.process(x * threshold, threshold) / threshold;
+                    let expected = shape(curve, x);
+                    assert!(
+                        (shaped - expected).abs() < 1e-3,
+                        "{} at {}: {} vs {}",
+                        name,
+                        x,
+                        shaped,
+                        expected
+                    );
+                }
+            }
+            // past the table the ends hold
+            assert!((shaper.process(10.0, 1.0) - shape(curve, TABLE_RANGE)).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn zero_threshold_stays_finite() {
+        let shaper = Waveshaper::default();
+        assert_eq!(shaper.process(0.0, 0.0), 0.0);
+        assert_eq!(shaper.process(0.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn custom_table_never_falls() {
+        let point_sets: [&[(f32, f32)]; 4] = [
+            &DEFAULT_CURVE_POINTS,
+            // a steep jump next to a flat shelf, where an unlimited spline would overshoot
+            &[(0.1, 0.0), (0.2, 0.9), (0.5, 0.9), (0.6, 1.0)],
+            &[(0.3, 0.8), (0.31, 0.8), (0.9, 0.2)],
+            &[],
+        ];
+        let mut shaper = Waveshaper::default();
+        shaper.set_curve(Curve::Custom);
+        for points in point_sets {
+            shaper.set_custom_points(&sanitize_curve_points(points));
+            let table = &shaper.tables[Curve::Custom as usize];
+            for pair in table.windows(2) {
+                assert!(pair[1] >= pair[0] - 1e-6, "{:?}: {:?}", points, pair);
+            }
+            // odd, the negative half mirrors the positive one
+            assert!((table[0] + table[TABLE_SIZE - 1]).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use crate::util::{one_pole_coef, sanitize};
+
+/*
+ * Per sample smoothing for params that zipper when they jump
+ */
+
+#[derive(Clone, Copy)]
+pub struct SmoothedParam {
+    value: f32,
+    coef: f32,
+    // nothing to glide from yet, the first target is taken as is
+    has_value: bool,
+}
+
+impl Default for SmoothedParam {
+    fn default() -> SmoothedParam {
+        SmoothedParam {
+            value: 0.0,
+            coef: 1.0,
+            has_value: false,
+        }
+    }
+}
+
+impl SmoothedParam {
+    // a time of zero follows the target instantly
+    pub fn set_time(&mut self, time_ms: f32, sample_rate: f32) {
+        self.coef = if time_ms > 0.0 {
+            one_pole_coef(time_ms, sample_rate)
+        } else {
+            1.0
+        };
+    }
+
+    pub fn next(&mut self, target: f32) -> f32 {
+        if !self.has_value {
+            self.value = target;
+            self.has_value = true;
+        }
+        self.value += (target - self.value) * self.coef;
+        self.value
+    }
+
+    // fills `buf` with the next steps towards `target`
+    pub fn fill(&mut self, buf: &mut [f32], target: f32) {
+        for sample in buf {
+            *sample = self.next(target);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.has_value = false;
+    }
+
+    pub fn sanitize(&mut self) -> bool {
+        sanitize(&mut self.value)
+    }
+}
@@ -1,24 +1,62 @@
-/**
+/*
  * Declare editer ui
  */
 use baseview::{Size, WindowOpenOptions, WindowScalePolicy};
-use std::{sync::Arc, sync::Mutex, time::Duration};
+use std::{
+    ops::RangeInclusive, sync::atomic::Ordering, sync::Arc, sync::Mutex, time::Duration,
+    time::Instant,
+};
 
 use egui::{
-    style::Margin, Color32, ColorImage, Context, FontData, FontDefinitions, FontFamily, FontId,
-    Frame, RichText, TextureHandle, Vec2,
+    pos2, style::Margin, Color32, ColorImage, Context, FontData, FontDefinitions, FontFamily,
+    FontId, Frame, Id, Pos2, Rect, Response, RichText, Sense, Shape, Stroke, TextureHandle, Ui,
+    Vec2,
 };
 use egui_baseview::{EguiWindow, Queue};
 
 use vst::editor::Editor;
+use vst::util::AtomicFloat;
 
-use crate::param::EffectParams;
+use crate::analysis::{clip_over_db, trim_to_threshold_db, BallisticLevel, Meters};
+use crate::param::{
+    crush_to_bits, drag_curve_point, freeze_note_text, snap, snap_levels, ClipMode, Curve,
+    EffectParams, GainMode, MeterBallistics, SilencePosition, StageOrder, TestSignalMode,
+    CLIP_MODE_NAMES, CRUSH_STEP, CRUSH_STEREO_NAMES, CURVE_NAMES, CURVE_POINTS_MAX,
+    DEFAULT_CURVE_POINTS, EMPHASIS_DB_MAX, FREEZE_CHANNEL_NAMES, FREEZE_NOTE_ANY, FREEZE_NOTE_MAX,
+    GAIN_MODE_NAMES, GAIN_RANGE_NAMES, GATE_CURVE_NAMES, GATE_RATE_NAMES, GATE_STEPS,
+    GLUE_ATTACK_MAX, GLUE_ATTACK_MIN, GLUE_HOLD_MAX, GLUE_RATIO_MAX, GLUE_RELEASE_MAX,
+    GLUE_RELEASE_MIN, INPUT_SOURCE_NAMES, INPUT_TRIM_DB_MAX, LEVEL_UNIT_NAMES,
+    METER_BALLISTICS_NAMES, MONO_MAKER_FREQ_MAX, MONO_MAKER_FREQ_MIN, PARAM_NUM, QUANT_LEVELS_MAX,
+    ROUTING_MODE_NAMES, SIDECHAIN_HPF_MAX, SIDECHAIN_HPF_MIN, SILENCE_POSITION_NAMES,
+    SMOOTHING_MS_MAX, STAGE_ORDER_NAMES, TEST_FREQ_MAX, TEST_FREQ_MIN, TEST_SIGNAL_NAMES,
+    WET_HPF_MAX, WET_HPF_MIN,
+};
+use crate::shaper::{MonotoneCubic, CUSTOM_CURVE_RANGE};
 use crate::util::WindowHandleNew;
 use crate::util::{to_db, to_linear};
 use crate::VstParent;
 
 const WINDOW_WIDTH: usize = 600;
 const WINDOW_HEIGHT: usize = 400;
+// the compact layout drops the side image panel
+const COMPACT_WINDOW_WIDTH: usize = 340;
+
+// how much slower sliders move while holding ctrl/cmd
+const FINE_DRAG_FACTOR: f32 = 0.1;
+
+// how long a control keeps its automation badge after the host last moved it
+const AUTOMATION_BADGE_SECS: f64 = 1.0;
+
+// span of the headroom gauge around the clamp threshold, and how close counts as touching
+const HEADROOM_GAUGE_SIZE: Vec2 = Vec2::new(200.0, 10.0);
+const HEADROOM_GAUGE_MIN_DB: f32 = -24.0;
+const HEADROOM_GAUGE_MAX_DB: f32 = 12.0;
+const HEADROOM_GAUGE_NEAR_DB: f32 = 3.0;
+
+const CURVE_EDITOR_SIZE: Vec2 = Vec2::new(220.0, 140.0);
+const CURVE_EDITOR_SEGMENTS: usize = 64;
+// how close the pointer has to be to pick up a curve point
+const CURVE_POINT_GRAB_PX: f32 = 10.0;
 
 pub struct PluginEditor {
     pub params: Arc<EffectParams>,
@@ -28,7 +66,8 @@ pub struct PluginEditor {
 
 impl Editor for PluginEditor {
     fn size(&self) -> (i32, i32) {
-        (WINDOW_WIDTH as i32, WINDOW_HEIGHT as i32)
+        let (width, height) = window_size(&self.params);
+        (width as i32, height as i32)
     }
 
     fn position(&self) -> (i32, i32) {
@@ -41,16 +80,23 @@ impl Editor for PluginEditor {
         }
         self.is_open = true;
 
+        // the window keeps the size it was opened with,
+        // a layout switch resizes it the next time the editor opens
+        let (width, height) = window_size(&self.params);
         let settings = WindowOpenOptions {
             title: String::from("Zippify"),
-            size: Size::new(WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64),
+            size: Size::new(width as f64, height as f64),
             scale: WindowScalePolicy::SystemScaleFactor,
             gl_config: Some(Default::default()),
         };
 
-        let side_image_texture: Arc<Mutex<Option<TextureHandle>>> = Arc::new(Mutex::new(None));
-        let side_image_texture_build = side_image_texture.clone();
-        let side_image_texture_update = side_image_texture;
+        // only the per-frame closure touches the image, it decodes it when first shown
+        let side_image = Mutex::new(SideImage::Unloaded);
+        // the readouts' ballistics run from frame to frame, so they live with the window
+        let meter_display = Mutex::new(MeterDisplay::default());
+
+        // what double-clicking a control resets it to
+        let defaults = EffectParams::default();
 
         let window_handle = EguiWindow::open_parented(
             &VstParent(parent),
@@ -62,46 +108,37 @@ impl Editor for PluginEditor {
                 // set to light mode
                 _egui_ctx.set_visuals(egui::Visuals::light());
                 // load custom font
+                // a font that fails to load is skipped, egui's default fonts fill in
                 let mut fonts = FontDefinitions::default();
-                fonts.font_data.insert(
-                    "Adventure".to_owned(),
-                    FontData::from_static(include_bytes!("./res/Adventure.ttf")),
-                );
-                fonts.font_data.insert(
-                    "RobotoLight".to_owned(),
-                    FontData::from_static(include_bytes!("./res/Roboto-Light.ttf")),
+                // put at first priority
+                add_font(
+                    &mut fonts,
+                    FontFamily::Monospace,
+                    "Adventure",
+                    include_bytes!("./res/Adventure.ttf"),
                 );
-                fonts.font_data.insert(
-                    "Roboto".to_owned(),
-                    FontData::from_static(include_bytes!("./res/Roboto-Regular.ttf")),
+                add_font(
+                    &mut fonts,
+                    FontFamily::Proportional,
+                    "Roboto",
+                    include_bytes!("./res/Roboto-Regular.ttf"),
                 );
-                // put at first priority
-                fonts
+                // the light family starts out as the regular one so it always exists
+                let proportional = fonts
                     .families
-                    .get_mut(&FontFamily::Monospace)
-                    .unwrap()
-                    .insert(0, "Adventure".to_owned());
+                    .get(&FontFamily::Proportional)
+                    .cloned()
+                    .unwrap_or_default();
                 fonts
                     .families
-                    .get_mut(&FontFamily::Proportional)
-                    .unwrap()
-                    .insert(0, "Roboto".to_owned());
-                fonts.families.insert(
+                    .insert(FontFamily::Name("RobotoLight".into()), proportional);
+                add_font(
+                    &mut fonts,
                     FontFamily::Name("RobotoLight".into()),
-                    Vec::from(["RobotoLight".to_owned()]),
+                    "RobotoLight",
+                    include_bytes!("./res/Roboto-Light.ttf"),
                 );
                 _egui_ctx.set_fonts(fonts);
-                let mut image_tex = side_image_texture_build.lock().unwrap();
-                *image_tex = Some(
-                    _egui_ctx.load_texture(
-                        "side-image",
-                        load_image_from_memory(include_bytes!(
-                            "./res/did_somepony_say_chocolate.jpg"
-                        ))
-                        .unwrap(),
-                        egui::TextureFilter::Linear,
-                    ),
-                );
 
                 // first frame's ui is broken, request a repaint to fix
                 _egui_ctx.request_repaint();
@@ -109,36 +146,49 @@ impl Editor for PluginEditor {
             // Called before each frame. Here you should update the state of your
             // application and build the UI.
             move |egui_ctx: &Context, _queue: &mut Queue, state: &mut Arc<EffectParams>| {
-                egui::SidePanel::right("image-panel")
-                    .frame(Frame {
-                        inner_margin: Margin {
-                            top: 85.0,
-                            right: 40.0,
+                // the compact layout keeps only the title and the controls
+                let is_compact = state.compact.get() > 0.5;
+                let (margin, heading_margin) = if is_compact {
+                    (12.0, 8.0)
+                } else {
+                    (40.0, 25.0)
+                };
+                let is_side_image = !is_compact && state.side_image.get() > 0.5;
+                if let Ok(mut side_image) = side_image.lock() {
+                    side_image.update(egui_ctx, is_side_image);
+                }
+                if is_side_image {
+                    egui::SidePanel::right("image-panel")
+                        .frame(Frame {
+                            inner_margin: Margin {
+                                top: 85.0,
+                                right: 40.0,
+                                ..Default::default()
+                            },
+                            fill: Color32::from_rgb(248, 248, 248),
                             ..Default::default()
-                        },
-                        fill: Color32::from_rgb(248, 248, 248),
-                        ..Default::default()
-                    })
-                    .resizable(false)
-                    .show(egui_ctx, |ui| {
-                        ui.image(
-                            (*side_image_texture_update.lock().unwrap())
-                                .as_ref()
-                                .unwrap(),
-                            Vec2::new(220.0, 220.0),
-                        );
-                        ui.hyperlink_to(
-                            "Image by Galaxy Swirl",
-                            "https://derpibooru.org/profiles/GalaxYSwiRL45",
-                        )
-                    });
+                        })
+                        .resizable(false)
+                        .show(egui_ctx, |ui| {
+                            // the side panel is left empty when the image fails to decode
+                            if let Ok(side_image) = side_image.lock() {
+                                if let SideImage::Loaded(texture) = &*side_image {
+                                    ui.image(texture, Vec2::new(220.0, 220.0));
+                                }
+                            }
+                            ui.hyperlink_to(
+                                "Image by Galaxy Swirl",
+                                "https://derpibooru.org/profiles/GalaxYSwiRL45",
+                            )
+                        });
+                }
                 egui::CentralPanel::default().show(egui_ctx, |_ui| {
                     egui::TopBottomPanel::top("top_panel")
                         .frame(Frame {
                             inner_margin: Margin {
-                                top: 25.0,
-                                left: 40.0,
-                                bottom: 25.0,
+                                top: heading_margin,
+                                left: margin,
+                                bottom: heading_margin,
                                 ..Default::default()
                             },
                             ..Default::default()
@@ -147,11 +197,15 @@ impl Editor for PluginEditor {
                             ui.heading(
                                 RichText::new("Zippify")
                                     .font(FontId {
-                                        size: 60.0,
+                                        size: if is_compact { 32.0 } else { 60.0 },
                                         family: FontFamily::Monospace,
                                     })
                                     .color(Color32::from_rgb(255, 107, 183)),
                             );
+                            draw_label(ui, state);
+                            if is_compact {
+                                return;
+                            }
                             ui.label(
                                 RichText::new("lil' & simple distortion/clipper plugin")
                                     .color(Color32::from_rgba_premultiplied(255, 107, 183, 204))
@@ -161,85 +215,40 @@ impl Editor for PluginEditor {
                                     }),
                             );
                         });
-                    egui::TopBottomPanel::bottom("bottom_panel")
-                        .frame(Frame {
-                            inner_margin: Margin {
-                                bottom: 25.0,
-                                left: 40.0,
+                    if !is_compact {
+                        egui::TopBottomPanel::bottom("bottom_panel")
+                            .frame(Frame {
+                                inner_margin: Margin {
+                                    bottom: 25.0,
+                                    left: 40.0,
+                                    ..Default::default()
+                                },
                                 ..Default::default()
-                            },
-                            ..Default::default()
-                        })
-                        .show(egui_ctx, |ui| {
-                            ui.label(RichText::new("made by grieferpig").font(FontId {
-                                size: 20.0,
-                                family: FontFamily::Name("RobotoLight".into()),
-                            }));
-                        });
+                            })
+                            .show(egui_ctx, |ui| {
+                                ui.label(RichText::new("made by grieferpig").font(FontId {
+                                    size: 20.0,
+                                    family: FontFamily::Name("RobotoLight".into()),
+                                }));
+                            });
+                    }
                     egui::CentralPanel::default()
                         .frame(Frame {
                             inner_margin: Margin {
-                                left: 40.0,
+                                left: margin,
                                 ..Default::default()
                             },
                             ..Default::default()
                         })
                         .show(egui_ctx, |ui| {
-                            let mut clamp_threshold = state.clamp_threshold.get();
-                            let mut is_lose_precision = state.lose_precision.get() > 0.5;
-                            let mut mix = state.mix.get();
-                            let mut gain = state.gain.get();
-
-                            let clamp_slider_text = if clamp_threshold > 0.15 {
-                                "Chocolate?"
-                            } else if clamp_threshold > 0.02 {
-                                "Chocolate!"
-                            } else {
-                                "CHOCOLATE!!!"
-                            };
-                            if ui
-                                .add(
-                                    egui::Slider::new(&mut clamp_threshold, 0.01..=1.0)
-                                        .text(clamp_slider_text)
-                                        .logarithmic(true),
-                                )
-                                .changed()
-                            {
-                                state.clamp_threshold.set(clamp_threshold)
-                            }
-                            ui.label(format!(
-                                "Clamp threshold: {:.2} dB",
-                                to_db(state.clamp_threshold.get())
-                            ));
-                            if ui
-                                .add(egui::Checkbox::new(&mut is_lose_precision, "8-bitify"))
-                                .changed()
-                            {
-                                state
-                                    .lose_precision
-                                    .set(if is_lose_precision { 1.0 } else { 0.0 })
-                            }
-                            if ui
-                                .add(egui::Slider::new(&mut mix, 0.0..=1.0).text("mix"))
-                                .changed()
-                            {
-                                state.mix.set(mix)
-                            }
-                            ui.label(format!("Mix: {:.2}%", state.mix.get() * 100.0));
-                            if ui
-                                .add(
-                                    egui::Slider::new(&mut gain, 1.0..=to_linear(24.53))
-                                        .text("gain"),
-                                )
-                                .changed()
-                            {
-                                state.gain.set(gain)
-                            }
-                            ui.label(format!("Gain: {:.2} dB", to_db(state.gain.get())));
+                            egui::ScrollArea::vertical()
+                                .show(ui, |ui| draw_params(ui, state, &defaults, &meter_display));
                         })
                 });
-                // update per 200 ms to follow param changes
-                egui_ctx.request_repaint_after(Duration::new(0, 200));
+                // update per 200 ms to follow param changes, the lite UI only redraws on input
+                if state.lite_ui.get() < 0.5 {
+                    egui_ctx.request_repaint_after(Duration::new(0, 200));
+                }
             },
         );
 
@@ -266,6 +275,1244 @@ impl Editor for PluginEditor {
     }
 }
 
+// the parameter controls, laid out top to bottom
+fn draw_params(
+    ui: &mut Ui,
+    state: &EffectParams,
+    defaults: &EffectParams,
+    meter_display: &Mutex<MeterDisplay>,
+) {
+    let clamp_threshold = state.clamp_threshold.get();
+    let meters = &state.meters;
+    let is_lite = state.lite_ui.get() > 0.5;
+
+    if !is_lite {
+        draw_meters(ui, state, meter_display);
+    }
+
+    param_slider(
+        ui,
+        &state.amount,
+        defaults.amount.get(),
+        0.0..=1.0,
+        "amount",
+        false,
+    )
+    .on_hover_text(
+        "One knob for everything, middle is as set, down cleans up, up clips harder and louder (Amount)",
+    );
+
+    ui.collapsing("Chain", |ui| draw_chain(ui, state));
+
+    ui.collapsing("Glue", |ui| {
+        param_checkbox(ui, &state.glue, "Glue")
+            .on_hover_text("Gently compresses before the clipper so it bites more evenly (Glue)");
+        param_slider(
+            ui,
+            &state.glue_threshold,
+            defaults.glue_threshold.get(),
+            0.01..=1.0,
+            "threshold",
+            true,
+        )
+        .on_hover_text("Level above which the glue starts compressing (Glue Thresh)");
+        param_slider(
+            ui,
+            &state.glue_ratio,
+            defaults.glue_ratio.get(),
+            1.0..=GLUE_RATIO_MAX,
+            "ratio",
+            false,
+        )
+        .on_hover_text("How strongly the glue compresses (Glue Ratio)");
+        param_slider(
+            ui,
+            &state.glue_attack,
+            defaults.glue_attack.get(),
+            GLUE_ATTACK_MIN..=GLUE_ATTACK_MAX,
+            "attack ms",
+            true,
+        )
+        .on_hover_text("How fast the glue reacts to louder parts (Glue Attack)");
+        param_slider(
+            ui,
+            &state.glue_release,
+            defaults.glue_release.get(),
+            GLUE_RELEASE_MIN..=GLUE_RELEASE_MAX,
+            "release ms",
+            true,
+        )
+        .on_hover_text("How fast the glue lets go afterwards (Glue Release)");
+        param_slider(
+            ui,
+            &state.glue_hold,
+            defaults.glue_hold.get(),
+            0.0..=GLUE_HOLD_MAX,
+            "hold ms",
+            false,
+        )
+        .on_hover_text(
+            "How long the glue waits after the last hit before letting go, stops chatter on busy drums (Glue Hold)",
+        );
+        param_slider(
+            ui,
+            &state.sidechain_hpf,
+            defaults.sidechain_hpf.get(),
+            SIDECHAIN_HPF_MIN..=SIDECHAIN_HPF_MAX,
+            "detector high-pass",
+            true,
+        )
+        .on_hover_text(
+            "Keeps the bass from pumping the glue, only the detector hears it, lowest is off (Glue SC HPF)",
+        );
+        ui.label(format!(
+            "Glue: {}, {:.1}:1",
+            state.format_level(state.glue_threshold.get()),
+            state.glue_ratio.get()
+        ));
+    });
+    param_slider(
+        ui,
+        &state.transient,
+        defaults.transient.get(),
+        0.0..=1.0,
+        "transient",
+        false,
+    )
+    .on_hover_text("Boosts the attack of each hit before the clipper for more punch (Transient)");
+
+    let clamp_slider_text = if clamp_threshold > 0.15 {
+        "Chocolate?"
+    } else if clamp_threshold > 0.02 {
+        "Chocolate!"
+    } else {
+        "CHOCOLATE!!!"
+    };
+    param_slider(
+        ui,
+        &state.clamp_threshold,
+        defaults.clamp_threshold.get(),
+        0.01..=1.0,
+        clamp_slider_text,
+        true,
+    )
+    .on_hover_text("Clip level, everything louder gets flattened to it (Chocolate!)");
+    ui.label(format!(
+        "Clamp threshold: {}",
+        state.format_level(state.clamp_threshold.get())
+    ));
+    // what actually reaches the clipper, glue, transient and emphasis included
+    // against the threshold the clipper last used, the amount macro and the drift move it
+    let clip_threshold = meters.clip_threshold.get();
+    let clip_over = clip_over_db(meters.clip_input_peak.get(), clip_threshold);
+    if !is_lite && clip_over.is_finite() {
+        let clip_over_text = if clip_over > 0.0 {
+            format!("Clipping {:.1} dB over the threshold", clip_over)
+        } else {
+            format!("{:.1} dB of headroom before clipping", -clip_over)
+        };
+        ui.label(clip_over_text)
+            .on_hover_text("Peak going into the clipper against the threshold");
+    }
+    if !is_lite {
+        headroom_gauge(ui, clip_over);
+    }
+    param_slider(
+        ui,
+        &state.input_trim,
+        defaults.input_trim.get(),
+        -INPUT_TRIM_DB_MAX..=INPUT_TRIM_DB_MAX,
+        "input trim dB",
+        false,
+    )
+    .on_hover_text("Level into the whole plugin, the dry signal included (Input Trim)");
+    // the clipper input hold is measured after the trim, drive, glue and all, so the new
+    // trim builds on the current one
+    if !is_lite
+        && ui
+            .button("Trim to threshold")
+            .on_hover_text(
+                "Sets the input trim so the loudest peak into the clipper since the last reset meets the clamp threshold",
+            )
+            .clicked()
+    {
+        let trim_db = trim_to_threshold_db(meters.clip_input_peak_hold.get(), clip_threshold);
+        if trim_db.is_finite() {
+            state.input_trim.set(
+                (state.input_trim.get() + trim_db).clamp(-INPUT_TRIM_DB_MAX, INPUT_TRIM_DB_MAX),
+            );
+            // the old peaks were measured at the old trim
+            meters.reset_peak_hold();
+        }
+    }
+    param_combo(ui, &state.clip_mode, &CLIP_MODE_NAMES, "clip mode").on_hover_text(
+        "Hard, split into lows and highs, only shave the peaks, or shape with a curve (Clip Mode)",
+    );
+    if ClipMode::from_index(state.clip_mode.get()) == ClipMode::Split {
+        // monitoring only, like the mono check it isn't saved or automated
+        ui.horizontal(|ui| {
+            for (solo, text) in [
+                (&state.solo_low, "Solo lows"),
+                (&state.solo_high, "Solo highs"),
+            ] {
+                let mut is_solo = solo.load(Ordering::Relaxed);
+                if ui.checkbox(&mut is_solo, text).changed() {
+                    solo.store(is_solo, Ordering::Relaxed);
+                }
+            }
+        })
+        .response
+        .on_hover_text(
+            "Hear only that band of the split clip, the dry signal drops out while soloed",
+        );
+    }
+    param_combo(ui, &state.curve, &CURVE_NAMES, "curve")
+        .on_hover_text("Shape used by the curve clip mode (Curve)");
+    if Curve::from_index(state.curve.get()) == Curve::Custom {
+        draw_curve_editor(ui, state);
+    }
+    param_slider(
+        ui,
+        &state.sat_floor,
+        defaults.sat_floor.get(),
+        0.0..=1.0,
+        "saturation floor",
+        false,
+    )
+    .on_hover_text(
+        "Rounds off the top of the range just under the threshold so the clip doesn't come in so suddenly, quieter parts stay clean (Sat Floor)",
+    );
+    param_slider(
+        ui,
+        &state.emphasis,
+        defaults.emphasis.get(),
+        0.0..=1.0,
+        "emphasis",
+        false,
+    )
+    .on_hover_text(
+        "Boosts the highs before clipping and cuts them after, for a less harsh clip (Emphasis)",
+    );
+    ui.label(format!(
+        "Emphasis: {:.1} dB",
+        state.emphasis.get() * EMPHASIS_DB_MAX
+    ));
+    param_slider(
+        ui,
+        &state.analog_drift,
+        defaults.analog_drift.get(),
+        0.0..=1.0,
+        "analog drift",
+        false,
+    )
+    .on_hover_text(
+        "Lets left and right clip at slightly different levels, like mismatched analog parts (Analog Drift)",
+    );
+    param_checkbox(ui, &state.output_ceiling, "Threshold = output ceiling").on_hover_text(
+        "Clips once more at the very end so the output never goes over the threshold (Ceiling)",
+    );
+    param_checkbox(ui, &state.ms_clip, "Clip mid/side separately").on_hover_text(
+        "Clips the middle and the sides of the stereo image on their own (M/S Clip)",
+    );
+    param_slider(
+        ui,
+        &state.mid_threshold,
+        defaults.mid_threshold.get(),
+        0.01..=1.0,
+        "mid",
+        true,
+    )
+    .on_hover_text("Clip level for the middle of the stereo image (Mid Thresh)");
+    param_slider(
+        ui,
+        &state.side_threshold,
+        defaults.side_threshold.get(),
+        0.01..=1.0,
+        "side",
+        true,
+    )
+    .on_hover_text("Clip level for the sides of the stereo image (Side Thresh)");
+    param_checkbox(ui, &state.side_limit, "Soft limit the side").on_hover_text(
+        "Leans the sides into their threshold instead of clipping them flat, the middle stays as it is (Side Limit)",
+    );
+    ui.label(format!(
+        "Mid: {}, side: {}",
+        state.format_level(state.mid_threshold.get()),
+        state.format_level(state.side_threshold.get())
+    ));
+    param_slider(
+        ui,
+        &state.tape,
+        defaults.tape.get(),
+        0.0..=1.0,
+        "tape",
+        false,
+    )
+    .on_hover_text("Warm tape style saturation with softer highs and gentle compression (Tape)");
+    ui.label(format!("Tape: {:.0}%", state.tape.get() * 100.0));
+    param_slider(ui, &state.wow, defaults.wow.get(), 0.0..=1.0, "wow", false)
+        .on_hover_text("Slow drifting pitch, like a stretched tape (Wow)");
+    param_slider(
+        ui,
+        &state.flutter,
+        defaults.flutter.get(),
+        0.0..=1.0,
+        "flutter",
+        false,
+    )
+    .on_hover_text("Fast pitch wobble from an uneven tape transport (Flutter)");
+    param_checkbox(ui, &state.lose_precision, "8-bitify")
+        .on_hover_text("Drops the resolution for a gritty, lo-fi sound (8-bitify)");
+    param_combo(ui, &state.stage_order, &STAGE_ORDER_NAMES, "order").on_hover_text(
+        "Crush the clipped sound, or crush first and let the clipper round off the steps (Stage Order)",
+    );
+    let crush_slider = param_slider(
+        ui,
+        &state.crush,
+        defaults.crush.get(),
+        0.0..=1.0,
+        "crush",
+        false,
+    )
+    .on_hover_text("How many bits 8-bitify keeps, fewer is dirtier (Crush)");
+    // whole bits only, same as host automation, so fine dragging has nothing to add here
+    if crush_slider.dragged() || crush_slider.changed() || crush_slider.double_clicked() {
+        state.crush.set(snap(state.crush.get(), CRUSH_STEP));
+        remember_value(ui, &state.crush);
+    }
+    ui.label(format!(
+        "Crush: {:.0} bits",
+        crush_to_bits(state.crush.get())
+    ));
+    let levels_slider = param_slider(
+        ui,
+        &state.quant_levels,
+        defaults.quant_levels.get(),
+        0.0..=QUANT_LEVELS_MAX,
+        "levels",
+        false,
+    )
+    .on_hover_text(
+        "Any number of levels instead of whole bits, below 2 follows crush (Quant Levels)",
+    );
+    if levels_slider.dragged() || levels_slider.changed() {
+        state
+            .quant_levels
+            .set(snap_levels(state.quant_levels.get()));
+        remember_value(ui, &state.quant_levels);
+    }
+    param_checkbox(ui, &state.mu_law, "Mu-law")
+        .on_hover_text("Telephone style companding, keeps more detail in quiet parts (Mu-law)");
+    param_combo(ui, &state.crush_stereo, &CRUSH_STEREO_NAMES, "crush stereo").on_hover_text(
+        "Crush left and right, or mid and side so the grit stays centered, or leave the side clean (Crush Stereo)",
+    );
+    param_checkbox(ui, &state.safety_lpf, "Safety low-pass").on_hover_text(
+        "Rolls off above 18 kHz, only while the clipper is driven hard (Safety LPF)",
+    );
+    param_combo(ui, &state.routing_mode, &ROUTING_MODE_NAMES, "routing")
+        .on_hover_text("Swaps the channels or feeds one side to both (Routing)");
+    ui.horizontal(|ui| {
+        param_checkbox(ui, &state.invert_l, "Invert L")
+            .on_hover_text("Flips the polarity of the left channel (Invert L)");
+        param_checkbox(ui, &state.invert_r, "Invert R")
+            .on_hover_text("Flips the polarity of the right channel (Invert R)");
+    });
+    param_slider(ui, &state.mix, defaults.mix.get(), 0.0..=1.0, "mix", false)
+        .on_hover_text("Balance between the processed and the original signal (Mix)");
+    ui.label(format!("Mix: {:.2}%", state.mix.get() * 100.0));
+    param_checkbox(ui, &state.dry_bleed, "Dry bleed")
+        .on_hover_text("Always lets a little of the original through, even fully wet (Dry Bleed)");
+    param_checkbox(ui, &state.dry_match, "Match dry tone").on_hover_text(
+        "Gives the original the same tape roll-off, so the mix changes the amount, not the tone (Dry Match)",
+    );
+    ui.horizontal(|ui| {
+        param_checkbox(ui, &state.compare, "Compare").on_hover_text(
+            "Plays the original signal in place of the processed one, for A/B checks (Compare)",
+        );
+        param_checkbox(ui, &state.level_match, "Level match").on_hover_text(
+            "Brings the original up or down to the loudness of the processed signal, so the louder one doesn't win the comparison (Level Match)",
+        );
+    });
+    param_checkbox(ui, &state.parallel, "Parallel, highs only").on_hover_text(
+        "Keeps the original at full level and mixes in only the added high harmonics (Parallel)",
+    );
+    if state.parallel.get() > 0.5 {
+        param_slider(
+            ui,
+            &state.wet_hpf,
+            defaults.wet_hpf.get(),
+            WET_HPF_MIN..=WET_HPF_MAX,
+            "wet high-pass",
+            true,
+        )
+        .on_hover_text(
+            "Everything of the processed signal below this is left out of the mix (Wet HPF)",
+        );
+    }
+    let (gain_min_db, gain_max_db) = state.gain_db_range();
+    db_slider(
+        ui,
+        &state.gain,
+        defaults.gain.get(),
+        gain_min_db..=gain_max_db,
+        "gain",
+    )
+    .on_hover_text("Makeup gain after the clipper, or drive into it, see the gain mode (Gain)");
+    ui.label(format!("Gain: {}", state.format_level(state.gain.get())));
+    param_combo(ui, &state.gain_mode, &GAIN_MODE_NAMES, "gain mode").on_hover_text(
+        "Raise the level after the clipper, push harder into it, or push and pull back out for the same level (Gain Mode)",
+    );
+    let gain_range = state.gain_range.get();
+    param_combo(ui, &state.gain_range, &GAIN_RANGE_NAMES, "gain range")
+        .on_hover_text("How far the gain slider reaches, the current gain is kept (Gain Range)");
+    if state.gain_range.get() != gain_range {
+        state.fit_gain_to_range();
+    }
+    param_checkbox(ui, &state.mono_maker, "Bass mono")
+        .on_hover_text("Makes the bass mono, keeps the highs stereo (Bass Mono)");
+    param_slider(
+        ui,
+        &state.mono_maker_freq,
+        defaults.mono_maker_freq.get(),
+        MONO_MAKER_FREQ_MIN..=MONO_MAKER_FREQ_MAX,
+        "mono freq",
+        true,
+    )
+    .on_hover_text("Everything below this frequency is made mono (Mono Freq)");
+    ui.label(format!("Mono below: {:.0} Hz", state.mono_maker_freq.get()));
+    param_checkbox(ui, &state.output_dither, "Dither output (16-bit)")
+        .on_hover_text("Adds shaped noise to hide quantization when rendering to 16-bit (Dither)");
+    ui.collapsing("Second clip stage", |ui| {
+        param_checkbox(ui, &state.clip2, "Clip again")
+            .on_hover_text("Runs the signal through a second clipper after the first one (Clip 2)");
+        param_combo(ui, &state.clip2_mode, &CLIP_MODE_NAMES, "clip mode")
+            .on_hover_text("How the second clipper bends the signal (Clip 2 Mode)");
+        param_slider(
+            ui,
+            &state.clip2_threshold,
+            defaults.clip2_threshold.get(),
+            0.01..=1.0,
+            "threshold",
+            true,
+        )
+        .on_hover_text("Clip level of the second clipper (Clip 2 Thresh)");
+        ui.label(format!(
+            "Second threshold: {}",
+            state.format_level(state.clip2_threshold.get())
+        ));
+    });
+    // momentary, only frozen while the button is held down
+    let freeze_button = ui
+        .add(egui::Button::new("Freeze (hold)").sense(Sense::click_and_drag()))
+        .on_hover_text("Loops the last moment of sound while held down (Freeze)");
+    if freeze_button.is_pointer_button_down_on() {
+        state.freeze.set(1.0);
+    } else if freeze_button.clicked() || freeze_button.drag_released() {
+        state.freeze.set(0.0);
+    }
+    let note_slider = param_slider(
+        ui,
+        &state.freeze_note,
+        defaults.freeze_note.get(),
+        FREEZE_NOTE_ANY..=FREEZE_NOTE_MAX,
+        "freeze note",
+        false,
+    )
+    .on_hover_text("MIDI note that holds the freeze, all the way left any note does (Freeze Note)");
+    if note_slider.dragged() || note_slider.changed() {
+        state.freeze_note.set(state.freeze_note.get().round());
+        remember_value(ui, &state.freeze_note);
+    }
+    ui.label(format!(
+        "Freeze note: {}",
+        freeze_note_text(state.freeze_note.get())
+    ));
+    param_combo(
+        ui,
+        &state.freeze_channel,
+        &FREEZE_CHANNEL_NAMES,
+        "freeze channel",
+    )
+    .on_hover_text("MIDI channel the freeze note is taken from (Freeze Channel)");
+    // also momentary, the editor owns this one so it just follows the button
+    let check_mono_button = ui
+        .add(egui::Button::new("Check mono (hold)").sense(Sense::click_and_drag()))
+        .on_hover_text("Sums left and right while held down to hear how it plays back in mono");
+    state.check_mono.store(
+        check_mono_button.is_pointer_button_down_on(),
+        Ordering::Relaxed,
+    );
+    if ui
+        .button("Panic")
+        .on_hover_text(
+            "Clears every filter, delay and loop, lets go of the freeze and fades back in",
+        )
+        .clicked()
+    {
+        state.freeze.set(0.0);
+        state.panic.store(true, Ordering::Relaxed);
+    }
+    ui.collapsing("Advanced", |ui| {
+        param_combo(
+            ui,
+            &state.input_source,
+            &INPUT_SOURCE_NAMES,
+            "read input from",
+        )
+        .on_hover_text(
+            "Where to read the audio from, only change this if your host needs it (Input Source)",
+        );
+        param_combo(
+            ui,
+            &state.silence_position,
+            &SILENCE_POSITION_NAMES,
+            "remove silence",
+        )
+        .on_hover_text(
+            "Whether near-silent tails are cut from the clean input or the distorted output (Silence Gate)",
+        );
+        param_checkbox(ui, &state.silence_link, "Link silence gate").on_hover_text(
+            "Both channels go quiet together, only once neither has signal (Silence Link)",
+        );
+        param_checkbox(ui, &state.legacy_silence, "Legacy silence gate").on_hover_text(
+            "The old gate that also cuts into the negative half of the wave, for older sessions (Legacy Gate)",
+        );
+        param_slider(
+            ui,
+            &state.smoothing_ms,
+            defaults.smoothing_ms.get(),
+            0.0..=SMOOTHING_MS_MAX,
+            "smoothing ms",
+            false,
+        )
+        .on_hover_text("How long gain, mix and the second threshold take to glide to a new value (Smoothing)");
+        param_slider(
+            ui,
+            &state.threshold_smoothing_ms,
+            defaults.threshold_smoothing_ms.get(),
+            0.0..=SMOOTHING_MS_MAX,
+            "threshold smoothing ms",
+            false,
+        )
+        .on_hover_text(
+            "How long the clamp threshold takes to glide, keep it near zero for LFO sweeps (Thresh Smooth)",
+        );
+        param_combo(ui, &state.level_unit, &LEVEL_UNIT_NAMES, "show levels in").on_hover_text(
+            "Shows thresholds and gain in dB or in percent of full scale (Level Unit)",
+        );
+        param_checkbox(ui, &state.true_peak_meter, "True peak meter").on_hover_text(
+            "Upsamples the output for the peak readout only, the audio is never oversampled (True Peak Mtr)",
+        );
+        param_checkbox(ui, &state.side_image, "Side image")
+            .on_hover_text("Turn off to skip loading the picture and free its memory (Side Image)");
+        param_checkbox(ui, &state.compact, "Compact layout").on_hover_text(
+            "Hides the side image, the window shrinks the next time it opens (Compact)",
+        );
+        param_checkbox(ui, &state.power_save, "Power save").on_hover_text(
+            "Stops processing after half a second of silence and fades back in when sound returns (Power Save)",
+        );
+        param_checkbox(ui, &state.lite_ui, "Lite UI").on_hover_text(
+            "Stops metering and the timed redraws, for sessions with lots of instances (Lite UI)",
+        );
+        ui.label(format!("Noise seed: {:.0}", state.seed.get()))
+            .on_hover_text("Keeps renders identical, saved with the project (Seed)");
+    });
+    ui.collapsing("Diagnostics", |ui| {
+        param_combo(ui, &state.test_signal, &TEST_SIGNAL_NAMES, "test signal").on_hover_text(
+            "Replaces the input with a built-in signal, to hear the effect without routing audio (Test Signal)",
+        );
+        if TestSignalMode::from_index(state.test_signal.get()) == TestSignalMode::Sine {
+            param_slider(
+                ui,
+                &state.test_freq,
+                defaults.test_freq.get(),
+                TEST_FREQ_MIN..=TEST_FREQ_MAX,
+                "test frequency",
+                true,
+            )
+            .on_hover_text("Frequency of the test sine (Test Freq)");
+        }
+        let state_resets = state.meters.state_resets.load(Ordering::Relaxed);
+        if state_resets > 0 {
+            ui.label(format!("Processing started over {} times", state_resets))
+                .on_hover_text(
+                    "The filters went non-finite, from garbage coming in or blowing up, and were cleared to recover",
+                );
+        }
+    });
+    ui.collapsing("Trance gate", |ui| {
+        param_checkbox(ui, &state.gate, "Gate")
+            .on_hover_text("Chops the sound in time with the song (Gate)");
+        param_combo(ui, &state.gate_rate, &GATE_RATE_NAMES, "step length")
+            .on_hover_text("Length of one gate step (Gate Rate)");
+        param_combo(ui, &state.gate_curve, &GATE_CURVE_NAMES, "fade").on_hover_text(
+            "Exponential eases in and out of each step, linear cuts harder (Gate Curve)",
+        );
+        if ui
+            .button("Restart pattern")
+            .on_hover_text(
+                "Starts the pattern over from step 1 right now, press it on the downbeat",
+            )
+            .clicked()
+        {
+            state.gate_reset.store(true, Ordering::Relaxed);
+        }
+        for row in state.gate_steps.chunks(GATE_STEPS / 2) {
+            ui.horizontal(|ui| {
+                for step in row {
+                    param_checkbox(ui, step, "").on_hover_text(
+                        "One gate step, ticked steps let the sound through (Gate Step)",
+                    );
+                }
+            });
+        }
+    });
+    host_value_tooltip(ui, state);
+}
+
+// levels of the last block, left out of the lite UI since process() stops writing them
+fn draw_meters(ui: &mut Ui, state: &EffectParams, meter_display: &Mutex<MeterDisplay>) {
+    let meters = &state.meters;
+
+    let ballistics = MeterBallistics::from_index(state.meter_ballistics.get());
+    let [input_peak, input_rms, output_peak, output_rms] = match meter_display.lock() {
+        Ok(mut meter_display) => meter_display.update(meters, ballistics),
+        Err(_) => meter_display_raw(meters),
+    };
+    ui.label(format!(
+        "In: {:.1} dB peak, {:.1} dB RMS",
+        to_db(input_peak),
+        to_db(input_rms)
+    ));
+    ui.label(format!(
+        "Out: {:.1} dB peak, {:.1} dB RMS",
+        to_db(output_peak),
+        to_db(output_rms)
+    ));
+    param_combo(ui, &state.meter_ballistics, &METER_BALLISTICS_NAMES, "ballistics").on_hover_text(
+        "How the readouts above move: every block as is, quick up and slow down, or slow averaging (Meter Ballistics)",
+    );
+    ui.horizontal(|ui| {
+        clip_led(ui, "L", meters.clip_reduction_l.get());
+        clip_led(ui, "R", meters.clip_reduction_r.get());
+    })
+    .response
+    .on_hover_text(
+        "Lights up per channel while the clipper is cutting, with how much it takes off",
+    );
+    ui.add(egui::ProgressBar::new(meters.envelope.get()).desired_width(200.0))
+        .on_hover_text("Output level envelope, the control signal other stages can follow");
+    // anything over 0 dBTP may clip in the DAC even when no sample does
+    let true_peak_hold = meters.output_true_peak_hold.get();
+    let true_peak_text = if state.true_peak_meter.get() > 0.5 {
+        format!(
+            "True peak: {:.1} dBTP, hold {:.1} dBTP",
+            to_db(meters.output_true_peak.get()),
+            to_db(true_peak_hold)
+        )
+    } else {
+        format!(
+            "Sample peak: {:.1} dB, hold {:.1} dB",
+            to_db(meters.output_true_peak.get()),
+            to_db(true_peak_hold)
+        )
+    };
+    if true_peak_hold > 1.0 {
+        ui.label(RichText::new(true_peak_text + " (over)").color(Color32::RED));
+    } else {
+        ui.label(true_peak_text);
+    }
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "Peak hold: in {:.1} dB, out {:.1} dB",
+            to_db(meters.input_peak_hold.get()),
+            to_db(meters.output_peak_hold.get())
+        ));
+        if ui
+            .button("Reset")
+            .on_hover_text("Clears the peak hold readouts")
+            .clicked()
+        {
+            meters.reset_peak_hold();
+        }
+    });
+}
+
+// how a stage in the chain view is switched
+enum StageSwitch<'a> {
+    Always,
+    // on/off param, toggled right from the chain view
+    Toggle(&'a AtomicFloat),
+    // switched by its amount in its own section, only shown here
+    Amount(bool),
+}
+
+// the instance label, edited on a copy so the lock isn't held while typing
+fn draw_label(ui: &mut Ui, state: &EffectParams) {
+    let mut label = state.label();
+    let response = ui
+        .add(
+            egui::TextEdit::singleline(&mut label)
+                .hint_text("label this instance")
+                .desired_width(220.0),
+        )
+        .on_hover_text("Saved with the session, handy when many instances are open");
+    if response.changed() {
+        state.set_label(&label);
+    }
+}
+
+// the custom curve's positive half, input across up to CUSTOM_CURVE_RANGE times the threshold.
+// Drag a point to move it, click empty space to add one, right click a point to drop it
+fn draw_curve_editor(ui: &mut Ui, state: &EffectParams) {
+    let mut points = state.curve_points();
+    let (response, painter) = ui.allocate_painter(CURVE_EDITOR_SIZE, Sense::click_and_drag());
+    let rect = response.rect;
+    let to_screen = |(x, y): (f32, f32)| {
+        pos2(
+            rect.left() + x * rect.width(),
+            rect.bottom() - y * rect.height(),
+        )
+    };
+    let from_screen = |pos: Pos2| {
+        (
+            ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0),
+            ((rect.bottom() - pos.y) / rect.height()).clamp(0.0, 1.0),
+        )
+    };
+    // the origin stays put, so it's never picked
+    let nearest = |points: &[(f32, f32)], pos: Pos2| {
+        (1..points.len())
+            .map(|index| (index, to_screen(points[index]).distance(pos)))
+            .filter(|&(_, distance)| distance < CURVE_POINT_GRAB_PX)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    };
+
+    let drag_id = response.id;
+    if response.drag_started() {
+        let grabbed = response
+            .interact_pointer_pos()
+            .and_then(|pos| nearest(&points, pos));
+        ui.data().insert_temp(drag_id, grabbed);
+    }
+    // a preset load mid drag can leave fewer points than were grabbed from
+    let grabbed = ui
+        .data()
+        .get_temp::<Option<usize>>(drag_id)
+        .flatten()
+        .filter(|&index| index < points.len());
+    if let (Some(index), Some(pos), true) =
+        (grabbed, response.interact_pointer_pos(), response.dragged())
+    {
+        points[index] = drag_curve_point(&points, index, from_screen(pos));
+        state.set_curve_points(&points);
+    } else if response.drag_released() {
+        ui.data().remove::<Option<usize>>(drag_id);
+    } else if let Some(pos) = response.hover_pos() {
+        if response.clicked() && points.len() < CURVE_POINTS_MAX && nearest(&points, pos).is_none()
+        {
+            let (x, y) = from_screen(pos);
+            let index = points
+                .iter()
+                .position(|&(px, _)| px > x)
+                .unwrap_or(points.len() - 1);
+            let y = y.clamp(points[index - 1].1, points[index].1);
+            points.insert(index, (x, y));
+            state.set_curve_points(&points);
+        } else if response.secondary_clicked() {
+            if let Some(index) = nearest(&points, pos).filter(|&index| index < points.len() - 1) {
+                points.remove(index);
+                state.set_curve_points(&points);
+            }
+        }
+    }
+
+    let accent = Color32::from_rgb(255, 107, 183);
+    let faint = Stroke::new(1.0, Color32::from_gray(200));
+    painter.rect_stroke(rect, 0.0, faint);
+    // where the input reaches the threshold
+    let threshold_x = rect.left() + rect.width() / CUSTOM_CURVE_RANGE;
+    painter.line_segment(
+        [
+            pos2(threshold_x, rect.top()),
+            pos2(threshold_x, rect.bottom()),
+        ],
+        faint,
+    );
+    let spline = MonotoneCubic::new(&points);
+    let line = (0..=CURVE_EDITOR_SEGMENTS)
+        .map(|step| {
+            let x = step as f32 / CURVE_EDITOR_SEGMENTS as f32;
+            to_screen((x, spline.eval(x)))
+        })
+        .collect();
+    painter.add(Shape::line(line, Stroke::new(2.0, accent)));
+    for &point in &points {
+        painter.circle_filled(to_screen(point), 4.0, accent);
+    }
+    response.on_hover_text(
+        "Custom curve up to twice the threshold, the line marks the threshold. Drag points to \
+         move them, click to add one, right click to remove one",
+    );
+    if ui.button("Reset curve").clicked() {
+        state.set_curve_points(&DEFAULT_CURVE_POINTS);
+    }
+}
+
+// the stages in the order process() runs them, keep the two in step
+fn draw_chain(ui: &mut Ui, state: &EffectParams) {
+    let mut stages = vec![
+        ("Channel routing", StageSwitch::Always),
+        (
+            "Invert polarity",
+            StageSwitch::Amount(state.invert_l.get() > 0.0 || state.invert_r.get() > 0.0),
+        ),
+        (
+            "Input trim",
+            StageSwitch::Amount(state.input_trim.get() != 0.0),
+        ),
+        ("Power save", StageSwitch::Always),
+        ("Remove silence", StageSwitch::Always),
+        ("Glue", StageSwitch::Toggle(&state.glue)),
+        (
+            "Transient emphasis",
+            StageSwitch::Amount(state.transient.get() > 0.0),
+        ),
+        (
+            "Pre-emphasis",
+            StageSwitch::Amount(state.emphasis.get() > 0.0),
+        ),
+        ("Clamp", StageSwitch::Always),
+        (
+            "Saturation floor",
+            StageSwitch::Amount(state.sat_floor.get() > 0.0),
+        ),
+        ("Clip again", StageSwitch::Toggle(&state.clip2)),
+        ("Mid/side clip", StageSwitch::Toggle(&state.ms_clip)),
+        ("Tape", StageSwitch::Amount(state.tape.get() > 0.0)),
+        (
+            "Wow and flutter",
+            StageSwitch::Amount(state.wow.get() > 0.0 || state.flutter.get() > 0.0),
+        ),
+        ("Gain", StageSwitch::Always),
+        ("8-bitify", StageSwitch::Toggle(&state.lose_precision)),
+        ("Safety low-pass", StageSwitch::Toggle(&state.safety_lpf)),
+        ("Bass mono", StageSwitch::Toggle(&state.mono_maker)),
+        ("Trance gate", StageSwitch::Toggle(&state.gate)),
+        ("Mix", StageSwitch::Always),
+        ("Freeze", StageSwitch::Toggle(&state.freeze)),
+        ("Dither", StageSwitch::Toggle(&state.output_dither)),
+        ("Output ceiling", StageSwitch::Toggle(&state.output_ceiling)),
+    ];
+    if SilencePosition::from_index(state.silence_position.get()) == SilencePosition::PostClip {
+        move_stage(&mut stages, "Remove silence", "8-bitify");
+    }
+    if StageOrder::from_index(state.stage_order.get()) == StageOrder::CrushFirst {
+        move_stage(&mut stages, "8-bitify", "Clamp");
+    }
+    // the drive goes into the clipper copy ahead of the glue, the compensation stays put
+    match GainMode::from_index(state.gain_mode.get()) {
+        GainMode::Output => (),
+        GainMode::Drive => {
+            rename_stage(&mut stages, "Gain", "Drive");
+            move_stage(&mut stages, "Drive", "Glue");
+        }
+        GainMode::Linked => {
+            rename_stage(&mut stages, "Gain", "Drive compensation");
+            if let Some(glue) = stages.iter().position(|(name, _)| *name == "Glue") {
+                stages.insert(glue, ("Drive", StageSwitch::Always));
+            }
+        }
+    }
+    for (position, (name, switch)) in stages.iter().enumerate() {
+        let text = format!("{}. {}", position + 1, name);
+        match switch {
+            StageSwitch::Always => {
+                ui.label(text);
+            }
+            StageSwitch::Toggle(param) => {
+                param_checkbox(ui, param, &text);
+            }
+            StageSwitch::Amount(is_active) => {
+                ui.label(format!(
+                    "{} ({})",
+                    text,
+                    if *is_active { "on" } else { "off" }
+                ));
+            }
+        }
+    }
+}
+
+// takes the stage out and puts it back right before `before`
+fn move_stage(stages: &mut Vec<(&str, StageSwitch)>, name: &str, before: &str) {
+    if let Some(from) = stages.iter().position(|(stage, _)| *stage == name) {
+        let stage = stages.remove(from);
+        let to = stages
+            .iter()
+            .position(|(stage, _)| *stage == before)
+            .unwrap_or(from);
+        stages.insert(to, stage);
+    }
+}
+
+fn rename_stage<'a>(stages: &mut [(&'a str, StageSwitch)], name: &str, new_name: &'a str) {
+    if let Some((stage, _)) = stages.iter_mut().find(|(stage, _)| *stage == name) {
+        *stage = new_name;
+    }
+}
+
+// slider for a continuous param
+// double-click resets it to its default, holding ctrl/cmd while dragging moves it finely
+fn param_slider(
+    ui: &mut Ui,
+    param: &AtomicFloat,
+    default: f32,
+    range: RangeInclusive<f32>,
+    text: &str,
+    logarithmic: bool,
+) -> Response {
+    mapped_slider(
+        ui,
+        param,
+        default,
+        (range, text, logarithmic),
+        (|value| value, |value| value),
+    )
+}
+
+// slider for a linear level that drags in dB, equal movement is an equal change in loudness
+fn db_slider(
+    ui: &mut Ui,
+    param: &AtomicFloat,
+    default: f32,
+    range_db: RangeInclusive<f32>,
+    text: &str,
+) -> Response {
+    mapped_slider(
+        ui,
+        param,
+        default,
+        (range_db, text, false),
+        (to_db, to_linear),
+    )
+}
+
+// the slider works on the shown value, the param keeps its own scale
+fn mapped_slider(
+    ui: &mut Ui,
+    param: &AtomicFloat,
+    default: f32,
+    (range, text, logarithmic): (RangeInclusive<f32>, &str, bool),
+    (to_shown, from_shown): (fn(f32) -> f32, fn(f32) -> f32),
+) -> Response {
+    let (min, max) = (*range.start(), *range.end());
+    let text = automation_text(ui, param, text);
+    let mut value = to_shown(param.get());
+    let response = ui.add(
+        egui::Slider::new(&mut value, range)
+            .text(text)
+            .logarithmic(logarithmic),
+    );
+    let (is_fine, pointer_delta) = {
+        let input = ui.input();
+        (input.modifiers.command, input.pointer.delta().x)
+    };
+
+    if response.double_clicked() {
+        param.set(default);
+    } else if response.dragged() && is_fine {
+        // ignore where the pointer is, only follow a fraction of its movement
+        let nudge = pointer_delta / ui.spacing().slider_width * FINE_DRAG_FACTOR;
+        let position = slider_position(to_shown(param.get()), min, max, logarithmic) + nudge;
+        param.set(from_shown(slider_value(
+            position.clamp(0.0, 1.0),
+            min,
+            max,
+            logarithmic,
+        )));
+    } else if response.changed() {
+        param.set(from_shown(value));
+    }
+    remember_value(ui, param);
+    remember_hover(ui, param, &response);
+    response
+}
+
+// the param under the pointer this frame, kept by its address like automation_id
+fn hovered_param_id() -> Id {
+    Id::new("hovered param")
+}
+
+fn remember_hover(ui: &Ui, param: &AtomicFloat, response: &Response) {
+    if response.hovered() {
+        ui.data()
+            .insert_temp(hovered_param_id(), param as *const AtomicFloat as usize);
+    }
+}
+
+// stacks under the control's own tooltip, for checking what automation lanes will hold
+fn host_value_tooltip(ui: &Ui, state: &EffectParams) {
+    let hovered = ui.data().get_temp::<usize>(hovered_param_id());
+    ui.data().remove::<usize>(hovered_param_id());
+    let index = hovered.and_then(|address| {
+        (0..PARAM_NUM).find(|&index| {
+            state.param_at(index).map_or(false, |param| {
+                param as *const AtomicFloat as usize == address
+            })
+        })
+    });
+    if let Some(index) = index {
+        egui::show_tooltip_text(ui.ctx(), hovered_param_id(), state.host_value_text(index));
+    }
+}
+
+// each control remembers the last value the editor left its param at, with when the host last
+// changed it behind the editor's back. The param's address tells the controls apart.
+fn automation_id(param: &AtomicFloat) -> Id {
+    Id::new(param as *const AtomicFloat as usize)
+}
+
+// true if the value moved since the editor last saw it, without the editor touching it
+fn is_host_change(seen: Option<f32>, value: f32) -> bool {
+    seen.map_or(false, |seen| seen != value)
+}
+
+// adds a badge to the control's text while the host is automating it
+fn automation_text(ui: &Ui, param: &AtomicFloat, text: &str) -> String {
+    let id = automation_id(param);
+    let now = ui.input().time;
+    let value = param.get();
+    let mut data = ui.data();
+    let (seen, changed_at) = match data.get_temp::<(f32, f64)>(id) {
+        Some((seen, changed_at)) => (Some(seen), changed_at),
+        None => (None, f64::NEG_INFINITY),
+    };
+    let changed_at = if is_host_change(seen, value) {
+        now
+    } else {
+        changed_at
+    };
+    data.insert_temp(id, (value, changed_at));
+
+    if now - changed_at < AUTOMATION_BADGE_SECS {
+        format!("{} [A]", text).trim_start().to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+// call after the editor itself wrote the param, so it isn't taken for automation
+fn remember_value(ui: &Ui, param: &AtomicFloat) {
+    let id = automation_id(param);
+    let mut data = ui.data();
+    let changed_at = data
+        .get_temp::<(f32, f64)>(id)
+        .map_or(f64::NEG_INFINITY, |(_, changed_at)| changed_at);
+    data.insert_temp(id, (param.get(), changed_at));
+}
+
+// maps a value to where it sits along the slider, 0..1
+fn slider_position(value: f32, min: f32, max: f32, logarithmic: bool) -> f32 {
+    if logarithmic {
+        (value / min).ln() / (max / min).ln()
+    } else {
+        (value - min) / (max - min)
+    }
+}
+
+fn slider_value(position: f32, min: f32, max: f32, logarithmic: bool) -> f32 {
+    if logarithmic {
+        min * (max / min).powf(position)
+    } else {
+        min + position * (max - min)
+    }
+}
+
+// checkbox for an on/off param stored as 0.0 / 1.0
+fn param_checkbox(ui: &mut Ui, param: &AtomicFloat, text: &str) -> Response {
+    let text = automation_text(ui, param, text);
+    let mut is_on = param.get() > 0.5;
+    let response = ui.add(egui::Checkbox::new(&mut is_on, text));
+    if response.changed() {
+        param.set(if is_on { 1.0 } else { 0.0 })
+    }
+    remember_value(ui, param);
+    remember_hover(ui, param, &response);
+    response
+}
+
+// combo box for a choice param stored as its index
+fn param_combo(ui: &mut Ui, param: &AtomicFloat, names: &[&str], text: &str) -> Response {
+    let text = automation_text(ui, param, text);
+    // compared against what was shown, not a fresh read, so automation landing
+    // while the frame is drawn isn't overwritten with the stale choice
+    let shown = param.get() as usize;
+    let mut selected = shown;
+    let response = egui::ComboBox::from_label(text)
+        .selected_text(names[selected])
+        .show_ui(ui, |ui| {
+            for (index, name) in names.iter().enumerate() {
+                ui.selectable_value(&mut selected, index, *name);
+            }
+        })
+        .response;
+    if selected != shown {
+        param.set(selected as f32)
+    }
+    remember_value(ui, param);
+    remember_hover(ui, param, &response);
+    response
+}
+
+// clip over reading as a bar, the tick is the threshold, empty while nothing comes in
+fn headroom_gauge(ui: &mut Ui, clip_over: f32) {
+    let (rect, response) = ui.allocate_exact_size(HEADROOM_GAUGE_SIZE, Sense::hover());
+    let position = |db: f32| {
+        let fraction =
+            (db - HEADROOM_GAUGE_MIN_DB) / (HEADROOM_GAUGE_MAX_DB - HEADROOM_GAUGE_MIN_DB);
+        rect.left() + fraction.clamp(0.0, 1.0) * rect.width()
+    };
+    let color = if clip_over > 0.0 {
+        Color32::RED
+    } else if clip_over > -HEADROOM_GAUGE_NEAR_DB {
+        Color32::from_rgb(255, 170, 0)
+    } else {
+        Color32::from_rgb(90, 190, 90)
+    };
+
+    let painter = ui.painter();
+    painter.rect_filled(rect, 2.0, Color32::from_gray(225));
+    if clip_over.is_finite() {
+        let bar = Rect::from_min_max(rect.min, pos2(position(clip_over), rect.max.y));
+        painter.rect_filled(bar, 2.0, color);
+    }
+    let threshold_x = position(0.0);
+    painter.line_segment(
+        [
+            pos2(threshold_x, rect.top() - 2.0),
+            pos2(threshold_x, rect.bottom() + 2.0),
+        ],
+        Stroke::new(2.0, Color32::from_gray(60)),
+    );
+    response.on_hover_text(format!(
+        "Clipper input peak from {:.0} to +{:.0} dB around the threshold, the mark is where clipping starts",
+        HEADROOM_GAUGE_MIN_DB, HEADROOM_GAUGE_MAX_DB
+    ));
+}
+
+// a dot that lights while the channel clips, followed by its gain reduction
+fn clip_led(ui: &mut Ui, channel: &str, reduction_db: f32) {
+    let color = if reduction_db > 0.01 {
+        Color32::RED
+    } else {
+        Color32::GRAY
+    };
+    ui.label(RichText::new("●").color(color));
+    ui.label(format!("{} clip {:.1} dB", channel, reduction_db));
+}
+
+// the decorative picture, decoded when first shown and freed again once hidden
+enum SideImage {
+    Unloaded,
+    Loaded(TextureHandle),
+    // failed to decode, not worth trying again every frame
+    Broken,
+}
+
+impl SideImage {
+    fn update(&mut self, ctx: &Context, is_shown: bool) {
+        match self {
+            SideImage::Unloaded if is_shown => {
+                *self = match load_image_from_memory(include_bytes!(
+                    "./res/did_somepony_say_chocolate.jpg"
+                )) {
+                    Ok(image) => SideImage::Loaded(ctx.load_texture(
+                        "side-image",
+                        image,
+                        egui::TextureFilter::Linear,
+                    )),
+                    Err(err) => {
+                        eprintln!("zippify: side image failed to load, skipping it: {}", err);
+                        SideImage::Broken
+                    }
+                }
+            }
+            // dropping the last handle frees the texture
+            SideImage::Loaded(_) if !is_shown => *self = SideImage::Unloaded,
+            _ => (),
+        }
+    }
+}
+
+// the in/out readouts as drawn, with the chosen ballistics applied from frame to frame
+#[derive(Default)]
+struct MeterDisplay {
+    // input peak, input RMS, output peak, output RMS
+    levels: [BallisticLevel; 4],
+    last_frame: Option<Instant>,
+}
+
+impl MeterDisplay {
+    fn update(&mut self, meters: &Meters, ballistics: MeterBallistics) -> [f32; 4] {
+        let now = Instant::now();
+        let dt = self
+            .last_frame
+            .map_or(0.0, |last_frame| (now - last_frame).as_secs_f32());
+        self.last_frame = Some(now);
+        let mut shown = meter_display_raw(meters);
+        for (level, shown) in self.levels.iter_mut().zip(&mut shown) {
+            *shown = level.update(*shown, ballistics, dt);
+        }
+        shown
+    }
+}
+
+fn meter_display_raw(meters: &Meters) -> [f32; 4] {
+    [
+        meters.input_peak.get(),
+        meters.input_rms.get(),
+        meters.output_peak.get(),
+        meters.output_rms.get(),
+    ]
+}
+
+// the compact layout only needs room for the controls
+fn window_size(params: &EffectParams) -> (usize, usize) {
+    if params.compact.get() > 0.5 {
+        (COMPACT_WINDOW_WIDTH, WINDOW_HEIGHT)
+    } else {
+        (WINDOW_WIDTH, WINDOW_HEIGHT)
+    }
+}
+
+// true for the TrueType and OpenType headers egui can parse
+fn is_font_data(data: &[u8]) -> bool {
+    matches!(
+        data.get(..4),
+        Some([0x00, 0x01, 0x00, 0x00])
+            | Some([b'O', b'T', b'T', b'O'])
+            | Some([b't', b'r', b'u', b'e'])
+    )
+}
+
+// registers a font at the front of a family, skipping data that isn't a font
+fn add_font(fonts: &mut FontDefinitions, family: FontFamily, name: &str, data: &'static [u8]) {
+    if !is_font_data(data) {
+        eprintln!("zippify: font {} failed to load, using the default", name);
+        return;
+    }
+    fonts
+        .font_data
+        .insert(name.to_owned(), FontData::from_static(data));
+    fonts
+        .families
+        .entry(family)
+        .or_default()
+        .insert(0, name.to_owned());
+}
+
 fn load_image_from_memory(image_data: &[u8]) -> Result<ColorImage, image::ImageError> {
     let image = image::load_from_memory(image_data)?;
     let size = [image.width() as _, image.height() as _];
@@ -273,3 +1520,30 @@ fn load_image_from_memory(image_data: &[u8]) -> Result<ColorImage, image::ImageE
     let pixels = image_buffer.as_flat_samples();
     Ok(ColorImage::from_rgba_unmultiplied(size, pixels.as_slice()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_unseen_moves_count_as_automation() {
+        // first frame, nothing to compare with yet
+        assert!(!is_host_change(None, 0.5));
+        // the editor wrote it and remembered it, or nobody touched it
+        assert!(!is_host_change(Some(0.5), 0.5));
+        // moved behind the editor's back
+        assert!(is_host_change(Some(0.5), 0.25));
+        assert!(is_host_change(Some(0.0), 1.0));
+    }
+
+    #[test]
+    fn bad_image_bytes_are_an_error_not_a_panic() {
+        let image_data = include_bytes!("./res/did_somepony_say_chocolate.jpg");
+        assert!(load_image_from_memory(&[]).is_err());
+        assert!(load_image_from_memory(b"not a picture at all").is_err());
+        // a real header cut short before the pixels
+        assert!(load_image_from_memory(&image_data[..64]).is_err());
+        let image = load_image_from_memory(image_data).unwrap();
+        assert!(image.size[0] > 0 && image.size[1] > 0);
+    }
+}
@@ -1,3 +1,6 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use baseview::WindowHandle;
 
 pub fn to_linear(db: f32) -> f32 {
@@ -5,9 +8,64 @@ pub fn to_linear(db: f32) -> f32 {
 }
 
 pub fn to_db(linear: f32) -> f32 {
+    // silence (or garbage) reads as -inf instead of NaN
+    if linear.is_nan() || linear <= 0.0 {
+        return f32::NEG_INFINITY;
+    }
     20.0 * linear.log10()
 }
 
+// per sample coefficient for a one-pole smoother settling in roughly `time_ms`
+pub fn one_pole_coef(time_ms: f32, sample_rate: f32) -> f32 {
+    1.0 - (-1000.0 / (time_ms * sample_rate)).exp()
+}
+
+// below this, recursive state is flushed to zero before it turns denormal
+const DENORMAL_FLOOR: f32 = 1e-20;
+
+// flushes tiny values to zero, returns false if the value is unusable
+pub fn sanitize(value: &mut f32) -> bool {
+    if !value.is_finite() {
+        return false;
+    }
+    if value.abs() < DENORMAL_FLOOR {
+        *value = 0.0;
+    }
+    true
+}
+
+// different every time, for seeding new instances
+pub fn random_seed() -> u32 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.subsec_nanos());
+    // instances opened within the same tick still get their own seed
+    static INSTANCE: AtomicU32 = AtomicU32::new(0);
+    let instance = INSTANCE.fetch_add(1, Ordering::Relaxed);
+    let mut rng = Rng::new(nanos ^ instance.wrapping_mul(0x9e37_79b9));
+    (rng.next_f32() * (1 << 24) as f32) as u32
+}
+
+// xorshift32, cheap enough to run per sample on the audio thread
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Rng {
+        // xorshift gets stuck at zero
+        Rng { state: seed.max(1) }
+    }
+
+    // uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
 pub struct WindowHandleNew {
     pub handle: WindowHandle,
 }